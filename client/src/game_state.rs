@@ -1,6 +1,10 @@
 use bevy::prelude::*;
 use std::collections::HashMap;
-use crate::map::Map;
+use maze_wars_protocol::damage::{MAX_HEALTH, SPAWN_ARMOR};
+use maze_wars_protocol::map_transfer::MapAssembler;
+use maze_wars_protocol::phase::MatchPhase;
+use maze_wars_protocol::weapon::Loadout;
+use maze_wars_protocol::{Map, PlayerId, PlayerSnapshot, TeamId};
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash, Default, States)]
 pub enum AppState {
@@ -11,16 +15,39 @@ pub enum AppState {
     GameOver,
 }
 
+/// The last `GameOver`, with names instead of player IDs.
+pub struct GameOverResults {
+    pub winner: String,
+    pub columns: Vec<String>,
+    pub rows: Vec<(String, Vec<i32>)>,
+    pub team_scores: Vec<(String, u32)>,
+}
+
+pub fn team_name(team: TeamId) -> String {
+    format!("Team {}", team + 1)
+}
+
 #[derive(Resource)]
 pub struct GameState {
     pub player_name: String,
-    pub player_id: Option<String>,
-    pub players: HashMap<String, (f32, f32, f32, bool)>,  // x, y, rotation, is_alive
+    pub player_id: Option<PlayerId>,
+    pub player_names: HashMap<PlayerId, String>,
+    pub teams: HashMap<PlayerId, TeamId>, // empty in game modes without teams
+    pub players: HashMap<PlayerId, PlayerSnapshot>,
     pub map: Option<Map>,
+    pub map_id: Option<u32>,
+    pub map_download: Option<MapAssembler>, // chunks of the map received so far
     pub map_rendered: bool,
-    pub last_shoot_time: f32,
+    pub loadout: Loadout, // weapon in hand and ammunition, predicted and corrected by snapshots
+    pub last_ammo_action: f64, // when we last fired or reloaded
     pub is_alive: bool,
-    pub game_over_results: Option<(String, Vec<(String, u32)>)>,
+    pub health: u32,
+    pub armor: u32,
+    pub last_damage: Option<(f32, (f32, f32))>, // when we were last hit, and where the shooter stood
+    pub phase: MatchPhase, // drives `AppState::GameOver`, see `handle_network_messages`
+    pub phase_ends_at: Option<f64>, // elapsed seconds when the server expects the phase to end
+    pub game_over_results: Option<GameOverResults>,
+    pub pending_impacts: Vec<(f32, f32)>, // wall impacts waiting for a decal
 }
 
 impl GameState {
@@ -28,13 +55,46 @@ impl GameState {
         Self {
             player_name,
             player_id: None,
+            player_names: HashMap::new(),
+            teams: HashMap::new(),
             players: HashMap::new(),
             map: None,
+            map_id: None,
+            map_download: None,
             map_rendered: false,
-            last_shoot_time: 0.0,
+            loadout: Loadout::default(),
+            last_ammo_action: 0.0,
             is_alive: true,
+            health: MAX_HEALTH,
+            armor: SPAWN_ARMOR,
+            last_damage: None,
+            phase: MatchPhase::default(),
+            phase_ends_at: None,
             game_over_results: None,
+            pending_impacts: Vec::new(),
         }
     }
-}
 
+    pub fn name_of(&self, id: PlayerId) -> String {
+        self.player_names.get(&id).cloned().unwrap_or_else(|| format!("#{}", id))
+    }
+
+    pub fn team_of(&self, id: PlayerId) -> Option<TeamId> {
+        self.teams.get(&id).copied()
+    }
+
+    /// Whether `id` plays on the local player's team.
+    pub fn is_teammate(&self, id: PlayerId) -> bool {
+        self.player_id.and_then(|me| self.team_of(me)).is_some_and(|team| self.team_of(id) == Some(team))
+    }
+
+    /// Where the other living players are, as the server last sent them.
+    /// They block the local player's predicted moves like on the server.
+    pub fn other_positions(&self) -> Vec<(f32, f32)> {
+        self.players
+            .iter()
+            .filter(|(&id, snapshot)| Some(id) != self.player_id && snapshot.is_alive)
+            .map(|(_, snapshot)| (snapshot.x, snapshot.y))
+            .collect()
+    }
+}
@@ -4,8 +4,13 @@ use bevy::window::CursorGrabMode;
 use crate::game_state::GameState;
 use crate::camera::{MouseSensitivity, PlayerCamera, PlayerRotation};
 use crate::network::NetworkSender;
-use crate::messages::ClientMessage;
-use crate::player::{Bullet, PLAYER_SPEED, SHOOT_COOLDOWN};
+use crate::player::Bullet;
+use maze_wars_protocol::ClientMessage;
+use maze_wars_protocol::movement::apply_move;
+use maze_wars_protocol::phase::MatchPhase;
+use maze_wars_protocol::weapon::WEAPONS;
+use crate::interpolation::{InterpolationSettings, SnapshotBuffers};
+use crate::prediction::InputHistory;
 
 #[derive(Resource)]
 pub struct CursorState {
@@ -15,6 +20,7 @@ pub struct CursorState {
 #[derive(Resource)]
 pub struct MovementTimer(pub Timer);
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn player_input(
     keyboard_input: Res<Input<KeyCode>>,
     mouse_input: Res<Input<MouseButton>>,
@@ -23,6 +29,9 @@ pub fn player_input(
     mut game_state: ResMut<GameState>,
     time: Res<Time>,
     mut timer: ResMut<MovementTimer>,
+    mut input_history: ResMut<InputHistory>,
+    snapshot_buffers: Res<SnapshotBuffers>,
+    interpolation: Res<InterpolationSettings>,
     player_rotation: Res<PlayerRotation>,
     mut query_set: ParamSet<(
         Query<&Transform, With<PlayerCamera>>,
@@ -35,11 +44,27 @@ pub fn player_input(
     let _ = windows;
     timer.0.tick(time.delta());
 
-    if mouse_input.just_pressed(MouseButton::Left) {
-        let current_time = time.elapsed_seconds();
-        if current_time - game_state.last_shoot_time >= SHOOT_COOLDOWN {
-            game_state.last_shoot_time = current_time;
+    // Changer d'arme avec 1, 2, 3
+    for (key, weapon) in [(KeyCode::Key1, 0), (KeyCode::Key2, 1), (KeyCode::Key3, 2)] {
+        if keyboard_input.just_pressed(key) && game_state.loadout.switch(weapon) {
+            println!("Switched to {}", WEAPONS[weapon as usize].name);
+        }
+    }
 
+    if keyboard_input.just_pressed(KeyCode::R) && game_state.loadout.reload(time.elapsed_seconds_f64()) {
+        game_state.last_ammo_action = time.elapsed_seconds_f64();
+        let reload_message = ClientMessage::Reload { weapon: game_state.loadout.current() };
+        if let Err(e) = network_sender.0.send(reload_message) {
+            eprintln!("Failed to send reload message: {}", e);
+        }
+    }
+
+    // Le serveur ignore les tirs en dehors du match
+    if mouse_input.just_pressed(MouseButton::Left) && game_state.phase == MatchPhase::InProgress {
+        let current_time = time.elapsed_seconds();
+        // Même règles que le serveur : cadence de tir, rechargement et munitions
+        if game_state.loadout.fire(time.elapsed_seconds_f64(), 0.0).is_ok() {
+            game_state.last_ammo_action = time.elapsed_seconds_f64();
             // Obtenir la transformation de la caméra
             let camera_transform_query = query_set.p0();
             let camera_transform = camera_transform_query
@@ -47,7 +72,13 @@ pub fn player_input(
                 .expect("Player camera not found");
             let shoot_direction = camera_transform.forward();
 
-            let shoot_message = ClientMessage::Shoot { direction: (shoot_direction.x, shoot_direction.z) };
+            let view_tick = snapshot_buffers.view_tick(current_time - interpolation.delay);
+            let shoot_message = ClientMessage::Shoot {
+                weapon: game_state.loadout.current(),
+                direction: (shoot_direction.x, shoot_direction.z),
+                pitch: player_rotation.pitch,
+                view_tick,
+            };
             if let Err(e) = network_sender.0.send(shoot_message) {
                 eprintln!("Failed to send shoot message: {}", e);
             }
@@ -113,15 +144,18 @@ pub fn player_input(
 
     if direction != Vec3::ZERO {
         direction = direction.normalize();
-        let move_message = ClientMessage::Move { direction: (direction.x, direction.z) };
+        let direction = (direction.x, direction.z);
+        let sequence = input_history.record(direction);
+        let move_message = ClientMessage::Move { sequence, direction };
         if let Err(e) = network_sender.0.send(move_message) {
             eprintln!("Failed to send move message: {}", e);
         }
-        let player_id = game_state.player_id.clone();
-        if let Some(player_id) = player_id {
-            if let Some(position) = game_state.players.get_mut(&player_id) {
-                position.0 += direction.x * PLAYER_SPEED;
-                position.1 += direction.z * PLAYER_SPEED;
+        // Prédiction locale avec les mêmes collisions que le serveur
+        let game_state = &mut *game_state;
+        if let (Some(map), Some(player_id)) = (&game_state.map, game_state.player_id) {
+            let others = game_state.other_positions();
+            if let Some(snapshot) = game_state.players.get_mut(&player_id) {
+                (snapshot.x, snapshot.y) = apply_move(map, (snapshot.x, snapshot.y), direction, &others);
             }
         }
     }
@@ -0,0 +1,104 @@
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::{PI, TAU};
+use bevy::prelude::*;
+use maze_wars_protocol::{PlayerId, PlayerSnapshot};
+
+const MAX_BUFFERED_SNAPSHOTS: usize = 32;
+
+/// How far in the past remote players are drawn, and how long their last
+/// movement is extended when snapshots stop arriving. Both in seconds.
+#[derive(Resource)]
+pub struct InterpolationSettings {
+    pub delay: f32,
+    pub max_extrapolation: f32,
+}
+
+impl Default for InterpolationSettings {
+    fn default() -> Self {
+        Self { delay: 0.1, max_extrapolation: 0.25 }
+    }
+}
+
+/// Recent snapshots of every remote player, stamped with their arrival time,
+/// along with the server tick of each snapshot.
+#[derive(Resource, Default)]
+pub struct SnapshotBuffers {
+    players: HashMap<PlayerId, VecDeque<(f32, PlayerSnapshot)>>,
+    ticks: VecDeque<(f32, u64)>,
+}
+
+impl SnapshotBuffers {
+    pub fn push_tick(&mut self, time: f32, tick: u64) {
+        if self.ticks.len() == MAX_BUFFERED_SNAPSHOTS {
+            self.ticks.pop_front();
+        }
+        self.ticks.push_back((time, tick));
+    }
+
+    /// Server tick of the snapshot closest to `render_time`, i.e. the world
+    /// as the player currently sees it. Sent with shots for lag compensation.
+    pub fn view_tick(&self, render_time: f32) -> u64 {
+        self.ticks
+            .iter()
+            .min_by(|(a, _), (b, _)| (a - render_time).abs().total_cmp(&(b - render_time).abs()))
+            .map_or(0, |&(_, tick)| tick)
+    }
+
+    pub fn push(&mut self, id: PlayerId, time: f32, snapshot: PlayerSnapshot) {
+        let buffer = self.players.entry(id).or_default();
+        if buffer.len() == MAX_BUFFERED_SNAPSHOTS {
+            buffer.pop_front();
+        }
+        buffer.push_back((time, snapshot));
+    }
+
+    /// Forgets players that are no longer part of the game.
+    pub fn retain(&mut self, mut keep: impl FnMut(PlayerId) -> bool) {
+        self.players.retain(|id, _| keep(*id));
+    }
+
+    /// Position and rotation of player `id` at `render_time`, blended between
+    /// the two snapshots around it or extrapolated past the newest one.
+    pub fn sample(&self, id: PlayerId, render_time: f32, max_extrapolation: f32) -> Option<PlayerSnapshot> {
+        let buffer = self.players.get(&id)?;
+        let &(newest_time, newest) = buffer.back()?;
+
+        if render_time >= newest_time {
+            let Some(&(previous_time, previous)) = buffer.iter().rev().nth(1) else {
+                return Some(newest);
+            };
+            let span = newest_time - previous_time;
+            if span <= f32::EPSILON {
+                return Some(newest);
+            }
+            let ahead = (render_time - newest_time).min(max_extrapolation);
+            return Some(blend(&previous, &newest, 1.0 + ahead / span));
+        }
+
+        let after_index = buffer.iter().position(|(time, _)| *time > render_time)?;
+        if after_index == 0 {
+            // Older than anything buffered
+            return Some(buffer[0].1);
+        }
+        let (before_time, before) = buffer[after_index - 1];
+        let (after_time, after) = buffer[after_index];
+        let t = (render_time - before_time) / (after_time - before_time);
+        Some(blend(&before, &after, t))
+    }
+}
+
+fn blend(from: &PlayerSnapshot, to: &PlayerSnapshot, t: f32) -> PlayerSnapshot {
+    // Tourner par le chemin le plus court
+    let mut turn = (to.rotation - from.rotation) % TAU;
+    if turn > PI {
+        turn -= TAU;
+    } else if turn < -PI {
+        turn += TAU;
+    }
+    PlayerSnapshot {
+        x: from.x + (to.x - from.x) * t,
+        y: from.y + (to.y - from.y) * t,
+        rotation: from.rotation + turn * t,
+        is_alive: to.is_alive,
+    }
+}
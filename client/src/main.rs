@@ -1,12 +1,12 @@
 mod game_state;
 mod player;
-mod map;
 mod network;
-mod messages;
 mod ui;
 mod camera;
 mod input;
 mod render;
+mod prediction;
+mod interpolation;
 use bevy::prelude::*;
 use game_state::{GameState, AppState};
 use network::{setup_network, NetworkReceiver, NetworkSender};
@@ -15,6 +15,7 @@ use input::CursorState;
 use std::io::{self, Write};
 use tokio::runtime::Runtime;
 use bevy::diagnostic::FrameTimeDiagnosticsPlugin;
+use maze_wars_protocol::player::MOVE_INTERVAL;
 fn main() -> io::Result<()> {
     println!("Enter server IP:port (e.g., 127.0.0.1:34254): ");
     io::stdout().flush()?;
@@ -27,26 +28,35 @@ fn main() -> io::Result<()> {
     io::stdin().read_line(&mut player_name)?;
     let player_name = player_name.trim().to_string();
     let rt = Runtime::new().unwrap();
-    let (_network_sender, network_receiver, client_sender) = rt.block_on(async {
+    let (_network_sender, network_receiver, client_sender, connection) = rt.block_on(async {
         setup_network(&server_addr, &player_name).await.unwrap()
     });
     App::new()
         .add_plugins(DefaultPlugins)
-        .add_plugin(FrameTimeDiagnosticsPlugin::default())
+        .add_plugin(FrameTimeDiagnosticsPlugin)
         .add_state::<AppState>()
         .insert_resource(GameState::new(player_name))
         .insert_resource(NetworkReceiver(network_receiver))
         .insert_resource(NetworkSender(client_sender))
+        .insert_resource(connection)
         .add_startup_system(render::setup_3d)
-        .insert_resource(input::MovementTimer(Timer::from_seconds(0.08, TimerMode::Repeating)))
+        .insert_resource(input::MovementTimer(Timer::from_seconds(MOVE_INTERVAL, TimerMode::Repeating)))
+        .insert_resource(prediction::InputHistory::default())
+        .insert_resource(interpolation::InterpolationSettings::default())
+        .insert_resource(interpolation::SnapshotBuffers::default())
         .add_startup_system(ui::setup_ui.after(render::setup_3d))
         .add_system(network::handle_network_messages)
+        .add_system(network::send_leave_on_exit)
         .add_system(input::player_input)
         .add_system(render::update_player_positions)
+        .add_system(render::tint_team_models)
         .add_system(render::render_map.in_schedule(OnEnter(AppState::RenderMap)))
         .add_system(ui::update_minimap)  
         .add_system(render::render_walls)
-        .add_system(ui::update_fps_text)  
+        .add_system(ui::update_fps_text)
+        .add_system(ui::update_health_hud)
+        .add_system(ui::update_ammo_hud)
+        .add_system(ui::update_match_hud)  
         .insert_resource(MouseSensitivity(0.005))
         .insert_resource(PlayerRotation::default())
         .add_system(input::player_look)
@@ -54,9 +64,12 @@ fn main() -> io::Result<()> {
         .insert_resource(CursorState { captured: true })
         .add_system(input::toggle_cursor_capture)
         .add_system(ui::game_over_screen.in_schedule(OnEnter(AppState::GameOver)))
+        .add_system(ui::clear_game_over_screen.in_schedule(OnExit(AppState::GameOver)))
         .add_system(ui::display_death_screen)
         .add_system(player::update_bullets)
         .add_system(render::update_visibility)
+        .add_system(render::spawn_impact_decals)
+        .add_system(render::update_impact_decals)
         .run();
     Ok(())
 }
\ No newline at end of file
@@ -1,14 +1,39 @@
 use std::net::UdpSocket;
 use bevy::prelude::*;
-use crate::messages::{ClientMessage, ServerMessage};
-use crate::game_state::{GameState, AppState};
+use bevy::app::AppExit;
+use bevy::window::WindowCloseRequested;
+use maze_wars_protocol::codec::CodecError;
+use maze_wars_protocol::damage::{MAX_HEALTH, SPAWN_ARMOR};
+use maze_wars_protocol::map_transfer::MapAssembler;
+use maze_wars_protocol::phase::MatchPhase;
+use maze_wars_protocol::reliability::Endpoint;
+use maze_wars_protocol::scoreboard::MatchWinner;
+use maze_wars_protocol::snapshot::{self, SnapshotReceiver};
+use maze_wars_protocol::weapon::Loadout;
+use maze_wars_protocol::{ClientMessage, PlayerId, ServerMessage, TeamId, HEARTBEAT_INTERVAL, MAX_PACKET_SIZE};
+use crate::game_state::{team_name, GameState, GameOverResults, AppState};
+use crate::interpolation::SnapshotBuffers;
+use crate::prediction::InputHistory;
 use crossbeam_channel::{unbounded, Receiver, Sender};
 #[derive(Resource)]
 pub struct NetworkReceiver(pub Receiver<ServerMessage>);
 #[derive(Resource)]
 pub struct NetworkSender(pub Sender<ClientMessage>);
-use std::sync::Arc;
-pub async fn setup_network(server_addr: &str, player_name: &str) -> Result<(Sender<ServerMessage>, Receiver<ServerMessage>, Sender<ClientMessage>), Box<dyn std::error::Error>> {
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Socket and reliability state shared with the network loops, to send
+/// what cannot wait for the send loop.
+#[derive(Resource, Clone)]
+pub struct ServerConnection {
+    socket: Arc<UdpSocket>,
+    endpoint: Arc<Mutex<Endpoint>>,
+}
+
+/// Silence after which the client assumes its session was lost and asks the
+/// server to resume it.
+const RECONNECT_AFTER: Duration = Duration::from_secs(3);
+pub async fn setup_network(server_addr: &str, player_name: &str) -> Result<(Sender<ServerMessage>, Receiver<ServerMessage>, Sender<ClientMessage>, ServerConnection), Box<dyn std::error::Error>> {
     let socket = UdpSocket::bind("0.0.0.0:0")?;
     socket.connect(server_addr)?;
     println!("Connected to server at {}", server_addr);
@@ -16,26 +41,77 @@ pub async fn setup_network(server_addr: &str, player_name: &str) -> Result<(Send
     let (client_sender, client_receiver) = unbounded::<ClientMessage>();
     let socket = Arc::new(socket);
     let send_socket = Arc::clone(&socket);
+    // Partagé par les deux boucles : numéros de séquence, acks et renvois
+    let endpoint = Arc::new(Mutex::new(Endpoint::new()));
+    let send_endpoint = Arc::clone(&endpoint);
+    let connection = ServerConnection { socket: Arc::clone(&socket), endpoint: Arc::clone(&endpoint) };
     // Envoyer le message de connexion
     let join_message = ClientMessage::Join { name: player_name.to_string() };
-    let serialized = serde_json::to_string(&join_message)?;
-    socket.send(serialized.as_bytes())?;
+    let serialized = endpoint.lock().unwrap().send(&join_message, Instant::now())?;
+    socket.send(&serialized)?;
     println!("Join message sent to server");
     // Clone network_sender pour l'utiliser dans la boucle de réception
     let network_sender_clone = network_sender.clone();
     
     // Lancer la boucle de réception
     tokio::spawn(async move {
-        let mut buf = vec![0u8; 4096];
+        let mut buf = vec![0u8; MAX_PACKET_SIZE];
+        let mut snapshots = SnapshotReceiver::default();
         loop {
             match socket.recv(&mut buf) {
                 Ok(n) => {
-                    if let Ok(message) = serde_json::from_slice::<ServerMessage>(&buf[..n]) {
-                        if let Err(e) = network_sender_clone.send(message) {
-                            eprintln!("Failed to send message to main thread: {}", e);
+                    let mut endpoint = endpoint.lock().unwrap();
+                    // Décoder chaque snapshot, même périmé : le serveur peut s'en servir de base.
+                    // Un snapshot dont la base est inconnue n'est pas acquitté, pour que le
+                    // serveur ne le prenne pas comme base à son tour
+                    let mut world = None;
+                    let received = endpoint.receive_if::<ServerMessage>(&buf[..n], Instant::now(), |message| {
+                        let ServerMessage::GameState { tick, baseline, players, removed, .. } = message else {
+                            return true;
+                        };
+                        world = snapshots.receive(*tick, *baseline, players, removed).cloned();
+                        if world.is_none() {
+                            eprintln!("Dropping snapshot {}: baseline {:?} unknown", tick, baseline);
+                        }
+                        world.is_some()
+                    });
+                    match received {
+                        Ok(Some(received)) => {
+                            // Le jeton accompagne désormais chaque paquet envoyé
+                            if let ServerMessage::Welcome { session, .. } = &received.message {
+                                endpoint.set_session(*session);
+                            }
+                            let mut message = received.message;
+                            if let ServerMessage::GameState { tick, last_input, ammo, clock, .. } = &message {
+                                let Some(world) = world.as_ref() else {
+                                    continue;
+                                };
+                                // Un snapshot plus ancien que le dernier paquet reçu est périmé
+                                if !received.is_latest {
+                                    continue;
+                                }
+                                // Le jeu reçoit toujours un snapshot complet
+                                let (players, _) = snapshot::diff(None, world);
+                                message = ServerMessage::GameState {
+                                    tick: *tick,
+                                    baseline: None,
+                                    players,
+                                    removed: Vec::new(),
+                                    last_input: *last_input,
+                                    ammo: *ammo,
+                                    clock: *clock,
+                                };
+                            }
+                            if let Err(e) = network_sender_clone.send(message) {
+                                eprintln!("Failed to send message to main thread: {}", e);
+                            }
                         }
-                    } else {
-                        eprintln!("Failed to parse server message");
+                        Ok(None) => {}
+                        Err(e @ (CodecError::Rejected(_) | CodecError::VersionMismatch { .. })) => {
+                            eprintln!("Cannot play on this server: {}", e);
+                            std::process::exit(1);
+                        }
+                        Err(e) => eprintln!("Failed to parse server message: {}", e),
                     }
                 }
                 Err(e) => eprintln!("Failed to receive data: {}", e),
@@ -43,65 +119,281 @@ pub async fn setup_network(server_addr: &str, player_name: &str) -> Result<(Send
         }
     });
     // Lancer la boucle d'envoi
+    let player_name = player_name.to_string();
     tokio::spawn(async move {
+        let mut last_reconnect: Option<Instant> = None;
         loop {
-            if let Ok(message) = client_receiver.try_recv() {
-                let serialized = serde_json::to_string(&message).unwrap();
-                if let Err(e) = send_socket.send(serialized.as_bytes()) {
+            let now = Instant::now();
+            let mut packets = Vec::new();
+            {
+                let mut endpoint = send_endpoint.lock().unwrap();
+                // Le serveur ne répond plus : repartir de zéro et reprendre la session
+                if let Some(session) = endpoint.session() {
+                    let silent = endpoint.last_received().is_none_or(|received| now.duration_since(received) >= RECONNECT_AFTER);
+                    if silent && last_reconnect.is_none_or(|sent| now.duration_since(sent) >= RECONNECT_AFTER) {
+                        println!("Lost contact with the server, reconnecting...");
+                        endpoint.reset();
+                        match endpoint.send(&ClientMessage::Reconnect { session, name: player_name.clone() }, now) {
+                            Ok(packet) => packets.push(packet),
+                            Err(e) => eprintln!("Failed to encode reconnect: {}", e),
+                        }
+                        last_reconnect = Some(now);
+                    }
+                }
+                if let Ok(message) = client_receiver.try_recv() {
+                    match endpoint.send(&message, now) {
+                        Ok(packet) => packets.push(packet),
+                        Err(e) => eprintln!("Failed to encode message: {}", e),
+                    }
+                }
+                // Garder la connexion en vie quand le joueur ne fait rien
+                if endpoint.last_sent().is_some_and(|sent| now.duration_since(sent) >= HEARTBEAT_INTERVAL) {
+                    match endpoint.send(&ClientMessage::Heartbeat, now) {
+                        Ok(packet) => packets.push(packet),
+                        Err(e) => eprintln!("Failed to encode heartbeat: {}", e),
+                    }
+                }
+                packets.extend(endpoint.resend_due(now));
+                if endpoint.ack_due(now) {
+                    packets.push(endpoint.ack(now));
+                }
+            }
+            for packet in packets {
+                if let Err(e) = send_socket.send(&packet) {
                     eprintln!("Failed to send message: {}", e);
                 }
             }
             tokio::time::sleep(std::time::Duration::from_millis(10)).await;
         }
     });
-    Ok((network_sender, network_receiver, client_sender))
+    Ok((network_sender, network_receiver, client_sender, connection))
 }
 
+/// Seconds after a local shot or reload during which snapshots may not
+/// reflect it yet, and do not overwrite the predicted ammunition.
+const AMMO_SYNC_DELAY: f64 = 0.5;
+
+#[allow(clippy::too_many_arguments)]
 pub(crate) fn handle_network_messages(
     mut commands: Commands,
     mut game_state: ResMut<GameState>,
     network_receiver: Res<NetworkReceiver>,
-    mut app_state: ResMut<NextState<AppState>>,
+    network_sender: Res<NetworkSender>,
+    mut input_history: ResMut<InputHistory>,
+    mut snapshot_buffers: ResMut<SnapshotBuffers>,
+    time: Res<Time>,
+    app_state: Res<State<AppState>>,
+    mut next_app_state: ResMut<NextState<AppState>>,
 ){
     for message in network_receiver.0.try_iter() {
         println!("Received message: {:?}", message);
         match message {
-            ServerMessage::Welcome { map, player_id } => {
-                println!("Received Welcome message with map");
-                game_state.map = Some(map);
+            ServerMessage::Welcome { player_id, name, .. } => {
+                if name != game_state.player_name {
+                    println!("Name {} is taken, playing as {}", game_state.player_name, name);
+                }
+                if game_state.player_id == Some(player_id) {
+                    println!("Reconnected as {}", name);
+                } else {
+                    println!("Joined as {}, waiting for the map", name);
+                    game_state.health = MAX_HEALTH;
+                    game_state.armor = SPAWN_ARMOR;
+                }
                 game_state.player_id = Some(player_id);
-                game_state.map_rendered = false;  // Force map re-render
-                
-                // Trigger map rendering
-                commands.insert_resource(NextState(Some(AppState::RenderMap)));
+                game_state.player_names.insert(player_id, name.clone());
+                game_state.player_name = name;
+            }
+            ServerMessage::MapChunk { info, index, data } => {
+                if game_state.map_id == Some(info.id) {
+                    // Carte déjà reçue (reprise de session) : confirmer une seule fois
+                    if index == 0 {
+                        send_map_received(&network_sender, info.id);
+                    }
+                    continue;
+                }
+                if game_state.map_download.as_ref().is_none_or(|download| download.info() != info) {
+                    game_state.map_download = Some(MapAssembler::new(info));
+                }
+                let map = game_state.map_download.as_mut().and_then(|download| download.add(info, index, data));
+                if let Some(map) = map {
+                    println!("Received the {}x{} map in {} chunks", map.map_width, map.map_height, info.chunk_count);
+                    game_state.map = Some(map);
+                    game_state.map_id = Some(info.id);
+                    game_state.map_download = None;
+                    game_state.map_rendered = false;  // Force map re-render
+
+                    // Confirmer la réception avant d'afficher la carte
+                    send_map_received(&network_sender, info.id);
+                    commands.insert_resource(NextState(Some(AppState::RenderMap)));
+                }
+            }
+            ServerMessage::PlayerJoined { player, name, team } => {
+                if Some(player) != game_state.player_id {
+                    println!("Player {} joined the game", name);
+                }
+                game_state.player_names.insert(player, name);
+                set_team(&mut game_state, player, team);
+            }
+            ServerMessage::PlayerTeam { player, team } => {
+                println!("Player {} moved to {}", game_state.name_of(player), team.map_or("no team".to_string(), team_name));
+                set_team(&mut game_state, player, team);
             }
-            ServerMessage::GameState { players } => {
+            ServerMessage::GameState { tick, players, last_input, ammo, clock, .. } => {
+                // Toujours complet, voir la boucle de réception
+                let Some(world) = snapshot::apply(None, &players, &[]) else {
+                    continue;
+                };
+                let players = snapshot::unpack_world(&world);
+                let now = time.elapsed_seconds();
+                snapshot_buffers.push_tick(now, tick);
+                for (&id, snapshot) in &players {
+                    if Some(id) != game_state.player_id {
+                        snapshot_buffers.push(id, now, *snapshot);
+                    }
+                }
+                snapshot_buffers.retain(|id| players.contains_key(&id));
                 game_state.players = players;
+                // Les munitions du serveur ne tiennent pas encore compte des derniers tirs envoyés
+                let now = time.elapsed_seconds_f64();
+                // Un snapshot parti avant le dernier PhaseChanged parle de la phase précédente
+                if clock.phase == game_state.phase {
+                    let has_end = clock.phase != MatchPhase::WaitingForPlayers;
+                    game_state.phase_ends_at = has_end.then_some(now + clock.remaining_ms as f64 / 1000.0);
+                }
+                if now - game_state.last_ammo_action >= AMMO_SYNC_DELAY {
+                    game_state.loadout.sync(ammo, now);
+                }
+                // Rejouer les entrées pas encore traitées par le serveur
+                let game_state = &mut *game_state;
+                if let (Some(map), Some(player_id)) = (&game_state.map, game_state.player_id) {
+                    let others = game_state.other_positions();
+                    if let Some(snapshot) = game_state.players.get_mut(&player_id) {
+                        (snapshot.x, snapshot.y) = input_history.reconcile(map, &others, (snapshot.x, snapshot.y), last_input);
+                    }
+                }
             }
-            ServerMessage::PlayerShot { shooter, target } => {
-                if Some(target.clone()) == game_state.player_id {
-                    println!("You were shot by {}!", shooter);
+            ServerMessage::PlayerShot { shooter, target, zone, damage, health, armor, from } => {
+                if Some(target) == game_state.player_id {
+                    println!("You were shot by {} ({:?}, {} damage)!", game_state.name_of(shooter), zone, damage);
+                    game_state.health = health;
+                    game_state.armor = armor;
+                    game_state.last_damage = Some((time.elapsed_seconds(), from));
+                } else if Some(shooter) == game_state.player_id {
+                    println!("You hit {} ({:?}, {} damage)", game_state.name_of(target), zone, damage);
                 } else {
-                    println!("Player {} was shot by {}!", target, shooter);
+                    println!("Player {} was shot by {}!", game_state.name_of(target), game_state.name_of(shooter));
+                }
+            }
+            ServerMessage::ShotFired { impact, hit_wall, .. } => {
+                if hit_wall {
+                    game_state.pending_impacts.push(impact);
                 }
             }
+            ServerMessage::PlayerLeft { player } => {
+                println!("Player {} left the game", game_state.name_of(player));
+                game_state.players.remove(&player);
+                game_state.player_names.remove(&player);
+                game_state.teams.remove(&player);
+                snapshot_buffers.retain(|id| id != player);
+            }
             ServerMessage::PlayerDied { player } => {
-                if Some(player.clone()) == game_state.player_id {
+                if Some(player) == game_state.player_id {
                     game_state.is_alive = false;
                     println!("You died!");
                 } else {
-                    println!("Player {} died!", player);
+                    println!("Player {} died!", game_state.name_of(player));
                 }
             }
-            ServerMessage::GameOver { winner, scores } => {
+            ServerMessage::PlayerRespawned { player, position } => {
+                if Some(player) == game_state.player_id {
+                    game_state.is_alive = true;
+                    game_state.health = MAX_HEALTH;
+                    game_state.armor = SPAWN_ARMOR;
+                    game_state.last_damage = None;
+                    game_state.loadout = Loadout::default();
+                    // Les entrées envoyées pendant la mort ne comptent plus
+                    input_history.clear();
+                    println!("You respawned!");
+                } else {
+                    println!("Player {} respawned", game_state.name_of(player));
+                }
+                // Pas d'interpolation depuis l'endroit de la mort
+                snapshot_buffers.retain(|id| id != player);
+                if let Some(snapshot) = game_state.players.get_mut(&player) {
+                    (snapshot.x, snapshot.y) = position;
+                    snapshot.is_alive = true;
+                }
+            }
+            ServerMessage::GameOver { winner, scoreboard } => {
+                let winner = match winner {
+                    MatchWinner::Player(id) => game_state.name_of(id),
+                    MatchWinner::Team(team) => team_name(team),
+                    MatchWinner::Draw => "Draw".to_string(),
+                };
+                let rows: Vec<(String, Vec<i32>)> = scoreboard.rows
+                    .into_iter()
+                    .map(|(id, values)| (game_state.name_of(id), values))
+                    .collect();
                 println!("Game Over! Winner: {}", winner);
-                println!("Scores:");
-                for (name, score) in &scores {
-                    println!("{}: {}", name, score);
+                println!("Scores: {}", scoreboard.columns.join(", "));
+                for (name, values) in &rows {
+                    println!("{}: {:?}", name, values);
+                }
+                // L'écran de fin s'affiche avec la phase PostMatch
+                let team_scores = scoreboard.team_scores
+                    .into_iter()
+                    .map(|(team, score)| (team_name(team), score))
+                    .collect();
+                game_state.game_over_results = Some(GameOverResults { winner, columns: scoreboard.columns, rows, team_scores });
+            }
+            ServerMessage::PhaseChanged { phase } => {
+                println!("Match phase: {:?}", phase);
+                game_state.phase = phase;
+                game_state.phase_ends_at = None; // jusqu'au prochain snapshot
+                if phase == MatchPhase::PostMatch {
+                    next_app_state.set(AppState::GameOver);
+                } else if app_state.0 == AppState::GameOver {
+                    // Retour à la carte, déjà affichée ou en cours de chargement
+                    next_app_state.set(AppState::RenderMap);
                 }
-                game_state.game_over_results = Some((winner, scores));
-                app_state.set(AppState::GameOver);
             }
         }
     }
-}
\ No newline at end of file
+}
+
+fn set_team(game_state: &mut GameState, player: PlayerId, team: Option<TeamId>) {
+    match team {
+        Some(team) => game_state.teams.insert(player, team),
+        None => game_state.teams.remove(&player),
+    };
+}
+
+fn send_map_received(network_sender: &NetworkSender, map_id: u32) {
+    if let Err(e) = network_sender.0.send(ClientMessage::MapReceived { map_id }) {
+        eprintln!("Failed to send map acknowledgement: {}", e);
+    }
+}
+
+/// Tells the server we are leaving instead of letting it time us out. Sent
+/// straight on the socket: the process exits before the send loop would get
+/// to it, and nothing is left to re-send it.
+pub fn send_leave_on_exit(
+    mut close_requests: EventReader<WindowCloseRequested>,
+    mut app_exits: EventReader<AppExit>,
+    connection: Res<ServerConnection>,
+    mut sent: Local<bool>,
+) {
+    if close_requests.iter().count() + app_exits.iter().count() == 0 || *sent {
+        return;
+    }
+    *sent = true;
+    let packet = connection.endpoint.lock().unwrap().send(&ClientMessage::Leave, Instant::now());
+    match packet {
+        Ok(packet) => {
+            if let Err(e) = connection.socket.send(&packet) {
+                eprintln!("Failed to send leave message: {}", e);
+            }
+        }
+        Err(e) => eprintln!("Failed to encode leave message: {}", e),
+    }
+}
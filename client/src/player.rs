@@ -1,14 +1,25 @@
 use bevy::prelude::*;
-
-pub const PLAYER_SPEED: f32 = 0.3;
-pub const SHOOT_COOLDOWN: f32 = 0.5;
+use maze_wars_protocol::{PlayerId, TeamId};
 
 #[derive(Component)]
 pub struct Player;
 
 #[derive(Component)]
 pub struct OtherPlayer {
-    pub name: String,
+    pub id: PlayerId,
+    pub team: Option<TeamId>, // the model is spawned again when it changes
+}
+
+/// Colour given to the materials of a model once its scene has loaded.
+#[derive(Component)]
+pub struct TeamTint(pub Color);
+
+pub fn team_color(team: TeamId) -> Color {
+    match team {
+        0 => Color::rgb(0.9, 0.25, 0.2),
+        1 => Color::rgb(0.2, 0.45, 0.95),
+        _ => Color::rgb(0.3, 0.85, 0.3),
+    }
 }
 
 #[derive(Component)]
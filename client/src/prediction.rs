@@ -0,0 +1,41 @@
+use std::collections::VecDeque;
+use bevy::prelude::*;
+use maze_wars_protocol::movement::apply_move;
+use maze_wars_protocol::Map;
+
+const MAX_PENDING_INPUTS: usize = 128;
+
+/// Moves sent to the server that it has not acknowledged yet, replayed on
+/// top of every snapshot so the local player does not snap back.
+#[derive(Resource, Default)]
+pub struct InputHistory {
+    next_sequence: u32,
+    pending: VecDeque<(u32, (f32, f32))>,
+}
+
+impl InputHistory {
+    /// Numbers a new input and remembers it until the server acks it.
+    pub fn record(&mut self, direction: (f32, f32)) -> u32 {
+        self.next_sequence += 1;
+        if self.pending.len() == MAX_PENDING_INPUTS {
+            self.pending.pop_front();
+        }
+        self.pending.push_back((self.next_sequence, direction));
+        self.next_sequence
+    }
+
+    pub fn clear(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Drops the inputs included in the snapshot and re-applies the others
+    /// to the authoritative position.
+    pub fn reconcile(&mut self, map: &Map, others: &[(f32, f32)], server_position: (f32, f32), last_input: u32) -> (f32, f32) {
+        while self.pending.front().is_some_and(|&(sequence, _)| sequence <= last_input) {
+            self.pending.pop_front();
+        }
+        self.pending
+            .iter()
+            .fold(server_position, |position, &(_, direction)| apply_move(map, position, direction, others))
+    }
+}
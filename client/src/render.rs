@@ -1,7 +1,10 @@
 use bevy::prelude::*;
+use maze_wars_protocol::damage::EYE_HEIGHT;
+use maze_wars_protocol::weapon::{weapon, WeaponId, WEAPONS};
 use crate::game_state::GameState;
-use crate::player::{Player, OtherPlayer};
+use crate::player::{team_color, Player, OtherPlayer, TeamTint};
 use crate::camera::{PlayerCamera, PlayerRotation};
+use crate::interpolation::{InterpolationSettings, SnapshotBuffers};
 
 
 pub fn setup_3d(mut commands: Commands) {
@@ -23,17 +26,18 @@ pub fn setup_3d(mut commands: Commands) {
 }
 
 
+#[allow(clippy::type_complexity)]
 pub fn render_map(
     mut commands: Commands,
     mut game_state: ResMut<GameState>,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    wall_query: Query<Entity, With<Wall>>,
+    map_query: Query<Entity, Or<(With<Wall>, With<Floor>)>>,
 ) {
     if let Some(map) = &game_state.map {
         if !game_state.map_rendered {
-            // Supprimer les anciens murs
-            for entity in wall_query.iter() {
+            // Supprimer les anciens murs et l'ancien sol
+            for entity in map_query.iter() {
                 commands.entity(entity).despawn();
             }
 
@@ -70,6 +74,7 @@ pub fn render_map(
                     transform: Transform::from_xyz(floor_size / 2.0 - 0.5, 0.0, floor_size / 2.0 - 0.5),
                     ..default()
                 },
+                Floor,
                 Renderable,
             ));
 
@@ -81,6 +86,9 @@ pub fn render_map(
 #[derive(Component)]
 pub struct Wall;
 
+#[derive(Component)]
+pub struct Floor;
+
 // Ajoutez ce composant
 #[derive(Component)]
 pub struct Walls {
@@ -106,31 +114,37 @@ pub fn render_walls(
     }
 }
 
+/// Viewmodel of the weapon in hand.
 #[derive(Component)]
-pub struct WeaponModel;
+pub struct WeaponModel {
+    pub weapon: WeaponId,
+}
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn update_player_positions(
     mut commands: Commands,
     game_state: Res<GameState>,
     player_rotation: Res<PlayerRotation>,
+    snapshot_buffers: Res<SnapshotBuffers>,
+    interpolation: Res<InterpolationSettings>,
+    time: Res<Time>,
     mut query_set: ParamSet<(
         Query<(Entity, &mut Transform), With<Player>>,
         Query<(Entity, &mut Transform, &OtherPlayer)>,
         Query<(Entity, &mut Transform), With<PlayerCamera>>,
-        Query<(Entity, &mut Transform), With<WeaponModel>>,
+        Query<(Entity, &mut Transform, &WeaponModel)>,
     )>,
     asset_server: Res<AssetServer>,
 ) {
-    if let Some(player_id) = &game_state.player_id {
-        if let Some(&(position_x, position_y, _, is_alive)) = game_state.players.get(player_id) {
-            if is_alive {
-                let eye_height = 1.6;
+    if let Some(player_id) = game_state.player_id {
+        if let Some(snapshot) = game_state.players.get(&player_id) {
+            if snapshot.is_alive {
                 let forward_offset = 0.01;
                 let mut camera_query = query_set.p2();
                 let new_camera_position = Vec3::new(
-                    position_x - forward_offset * player_rotation.yaw.sin(),
-                    eye_height,
-                    position_y - forward_offset * player_rotation.yaw.cos()
+                    snapshot.x - forward_offset * player_rotation.yaw.sin(),
+                    EYE_HEIGHT,
+                    snapshot.y - forward_offset * player_rotation.yaw.cos()
                 );
                 let new_camera_rotation = Quat::from_euler(EulerRot::YXZ, player_rotation.yaw, player_rotation.pitch, 0.0);
 
@@ -149,24 +163,39 @@ pub fn update_player_positions(
                 }
 
                 // Gestion du modèle de l'arme
-                let weapon_offset = Vec3::new(0.04, -0.15, -0.3); // Pousser l'arme plus vers le centre
+                let current_weapon = game_state.loadout.current();
+                // Pendant le rechargement, l'arme plonge hors de l'écran puis remonte
+                let dip = game_state.loadout
+                    .reload_progress(current_weapon, time.elapsed_seconds_f64())
+                    .map_or(0.0, |progress| (progress * std::f32::consts::PI).sin());
+                let weapon_offset = Vec3::new(0.04, -0.15 - 0.12 * dip, -0.3); // Pousser l'arme plus vers le centre
+                let weapon_rotation = new_camera_rotation * Quat::from_rotation_x(-0.9 * dip);
                 let mut weapon_query = query_set.p3();
 
-                if let Ok((_, mut weapon_transform)) = weapon_query.get_single_mut() {
-                    weapon_transform.translation = new_camera_position + new_camera_rotation * weapon_offset;
-                    weapon_transform.rotation = new_camera_rotation;
-                } else {
+                let mut held = false;
+                for (entity, mut weapon_transform, model) in weapon_query.iter_mut() {
+                    if model.weapon == current_weapon && !held {
+                        weapon_transform.translation = new_camera_position + new_camera_rotation * weapon_offset;
+                        weapon_transform.rotation = weapon_rotation;
+                        held = true;
+                    } else {
+                        // Arme rangée : retirer son modèle
+                        commands.entity(entity).despawn_recursive();
+                    }
+                }
+                if !held {
+                    let def = weapon(current_weapon).unwrap_or(&WEAPONS[0]);
                     commands.spawn((
                         SceneBundle {
-                            scene: asset_server.load("models/player/ak.glb#Scene0"),
+                            scene: asset_server.load(def.model),
                             transform: Transform {
                                 translation: new_camera_position + new_camera_rotation * weapon_offset,
-                                rotation: new_camera_rotation,
-                                scale: Vec3::splat(0.15), // Réduire la taille de l'arme
+                                rotation: weapon_rotation,
+                                scale: Vec3::splat(def.model_scale),
                             },
                             ..default()
                         },
-                        WeaponModel,
+                        WeaponModel { weapon: current_weapon },
                     ));
                 }
 
@@ -179,13 +208,18 @@ pub fn update_player_positions(
         }
     }
 
-    // Update other players
+    // Update other players, drawn slightly in the past between two snapshots
+    let render_time = time.elapsed_seconds() - interpolation.delay;
     let mut other_player_query = query_set.p1();
     for (entity, mut transform, other_player) in other_player_query.iter_mut() {
-        if let Some(&(x, y, rotation, is_alive)) = game_state.players.get(&other_player.name) {
-            if is_alive {
-                transform.translation = Vec3::new(x, 0.0, y);
-                transform.rotation = Quat::from_rotation_y(rotation);
+        if let Some(snapshot) = game_state.players.get(&other_player.id) {
+            // Changé d'équipe : le modèle est recréé avec la bonne couleur
+            if snapshot.is_alive && game_state.team_of(other_player.id) == other_player.team {
+                let shown = snapshot_buffers
+                    .sample(other_player.id, render_time, interpolation.max_extrapolation)
+                    .unwrap_or(*snapshot);
+                transform.translation = Vec3::new(shown.x, 0.0, shown.y);
+                transform.rotation = Quat::from_rotation_y(shown.rotation);
             } else {
                 commands.entity(entity).despawn_recursive();
             }
@@ -195,25 +229,102 @@ pub fn update_player_positions(
     }
 
     // Add new other players
-    for (name, &(x, y, rotation, is_alive)) in game_state.players.iter() {
-        if Some(name) != game_state.player_id.as_ref() && is_alive {
+    for (&id, snapshot) in game_state.players.iter() {
+        if Some(id) != game_state.player_id && snapshot.is_alive {
             let other_player_query = query_set.p1();
-            if !other_player_query.iter().any(|(_, _, op)| &op.name == name) {
-                commands.spawn((
+            if !other_player_query.iter().any(|(_, _, op)| op.id == id) {
+                let team = game_state.team_of(id);
+                let mut model = commands.spawn((
                     SceneBundle {
                         scene: asset_server.load("models/player/Soldier.glb#Scene0"),
-                        transform: Transform::from_xyz(x, 0.0, y)
-                            .with_rotation(Quat::from_rotation_y(rotation))
+                        transform: Transform::from_xyz(snapshot.x, 0.0, snapshot.y)
+                            .with_rotation(Quat::from_rotation_y(snapshot.rotation))
                             .with_scale(Vec3::splat(0.06)),
                         ..default()
                     },
-                    OtherPlayer { name: name.clone() },
+                    OtherPlayer { id, team },
                 ));
+                if let Some(team) = team {
+                    model.insert(TeamTint(team_color(team)));
+                }
             }
         }
     }
 }
 
+/// Tints the materials of team players' models. Scenes load in the
+/// background, so this waits for their meshes before removing `TeamTint`.
+pub fn tint_team_models(
+    mut commands: Commands,
+    tinted: Query<(Entity, &TeamTint)>,
+    children: Query<&Children>,
+    mesh_materials: Query<&Handle<StandardMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (entity, tint) in tinted.iter() {
+        let mut found = false;
+        for descendant in children.iter_descendants(entity) {
+            let Ok(handle) = mesh_materials.get(descendant) else {
+                continue;
+            };
+            // Chaque modèle a ses propres matériaux, partagés sinon avec tous les autres
+            let Some(mut material) = materials.get(handle).cloned() else {
+                continue;
+            };
+            material.base_color = tint.0;
+            commands.entity(descendant).insert(materials.add(material));
+            found = true;
+        }
+        if found {
+            commands.entity(entity).remove::<TeamTint>();
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct ImpactDecal {
+    pub lifetime: Timer,
+}
+
+pub fn spawn_impact_decals(
+    mut commands: Commands,
+    mut game_state: ResMut<GameState>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if game_state.pending_impacts.is_empty() {
+        return;
+    }
+    let decal_mesh = meshes.add(Mesh::from(shape::Box::new(0.08, 0.08, 0.08)));
+    let decal_material = materials.add(Color::rgb(0.1, 0.1, 0.1).into());
+    for (x, y) in game_state.pending_impacts.drain(..) {
+        commands.spawn((
+            PbrBundle {
+                mesh: decal_mesh.clone(),
+                material: decal_material.clone(),
+                transform: Transform::from_xyz(x, 1.5, y),
+                ..default()
+            },
+            ImpactDecal {
+                lifetime: Timer::from_seconds(5.0, TimerMode::Once),
+            },
+        ));
+    }
+}
+
+pub fn update_impact_decals(
+    mut commands: Commands,
+    mut decals: Query<(Entity, &mut ImpactDecal)>,
+    time: Res<Time>,
+) {
+    for (entity, mut decal) in decals.iter_mut() {
+        decal.lifetime.tick(time.delta());
+        if decal.lifetime.finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
 #[derive(Component)]
 pub struct Renderable;
 
@@ -1,45 +1,67 @@
 use std::time::Instant;
 
 use bevy::prelude::*;
+use maze_wars_protocol::phase::MatchPhase;
+use maze_wars_protocol::weapon::weapon;
+use crate::camera::PlayerRotation;
 use crate::game_state::GameState;
+use crate::player::team_color;
 use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
 
+#[derive(Component)]
+pub struct GameOverScreen;
+
 pub fn game_over_screen(
     mut commands: Commands,
     game_state: Res<GameState>,
     asset_server: Res<AssetServer>,
 ) {
-    if let Some((winner, scores)) = &game_state.game_over_results {
-        commands.spawn(NodeBundle {
-            style: Style {
-                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
-                flex_direction: FlexDirection::Column,
-                justify_content: JustifyContent::Center,
-                align_items: AlignItems::Center,
+    if let Some(results) = &game_state.game_over_results {
+        commands.spawn((
+            NodeBundle {
+                style: Style {
+                    size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::Center,
+                    align_items: AlignItems::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
                 ..default()
             },
-            background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
-            ..default()
-        }).with_children(|parent| {
+            GameOverScreen,
+        )).with_children(|parent| {
             parent.spawn(TextBundle::from_section(
-                format!("Game Over!\nWinner: {}", winner),
+                format!("Game Over!\nWinner: {}", results.winner),
                 TextStyle {
                     font: asset_server.load("fonts/FiraSans-Medium.ttf"),
                     font_size: 40.0,
                     color: Color::WHITE,
                 },
             ));
+            if !results.team_scores.is_empty() {
+                let team_scores: Vec<String> = results.team_scores.iter().map(|(team, score)| format!("{}: {}", team, score)).collect();
+                parent.spawn(TextBundle::from_section(
+                    team_scores.join("   "),
+                    TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Medium.ttf"),
+                        font_size: 30.0,
+                        color: Color::WHITE,
+                    },
+                ));
+            }
             parent.spawn(TextBundle::from_section(
-                "Scores:",
+                format!("Scores: {}", results.columns.join(" / ")),
                 TextStyle {
                     font: asset_server.load("fonts/FiraSans-Medium.ttf"),
                     font_size: 30.0,
                     color: Color::WHITE,
                 },
             ));
-            for (name, score) in scores {
+            for (name, values) in &results.rows {
+                let values: Vec<String> = values.iter().map(|value| value.to_string()).collect();
                 parent.spawn(TextBundle::from_section(
-                    format!("{}: {}", name, score),
+                    format!("{}: {}", name, values.join(" / ")),
                     TextStyle {
                         font: asset_server.load("fonts/FiraSans-Medium.ttf"),
                         font_size: 20.0,
@@ -51,13 +73,35 @@ pub fn game_over_screen(
     }
 }
 
+/// Removes the game over screen when the next match gets going.
+pub fn clear_game_over_screen(mut commands: Commands, screens: Query<Entity, With<GameOverScreen>>) {
+    for entity in screens.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+#[derive(Component)]
+pub struct DeathScreen;
+
+/// Shows the death screen while the local player is dead, and removes it
+/// once they respawn.
 pub fn display_death_screen(
     mut commands: Commands,
     game_state: Res<GameState>,
     asset_server: Res<AssetServer>,
+    death_screens: Query<Entity, With<DeathScreen>>,
 ) {
-    if !game_state.is_alive {
-        commands.spawn(NodeBundle {
+    if game_state.is_alive {
+        for entity in death_screens.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        return;
+    }
+    if !death_screens.is_empty() {
+        return;
+    }
+    commands.spawn((
+        NodeBundle {
             style: Style {
                 size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
                 position_type: PositionType::Absolute,
@@ -67,17 +111,18 @@ pub fn display_death_screen(
             },
             background_color: Color::rgba(0.0, 0.0, 0.0, 0.8).into(),
             ..default()
-        }).with_children(|parent| {
-            parent.spawn(TextBundle::from_section(
-                "You were killed!",
-                TextStyle {
-                    font: asset_server.load("fonts/FiraSans-Medium.ttf"),
-                    font_size: 40.0,
-                    color: Color::RED,
-                },
-            ));
-        });
-    }
+        },
+        DeathScreen,
+    )).with_children(|parent| {
+        parent.spawn(TextBundle::from_section(
+            "You were killed!",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Medium.ttf"),
+                font_size: 40.0,
+                color: Color::RED,
+            },
+        ));
+    });
 }
 
 pub fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
@@ -121,6 +166,116 @@ pub fn setup_ui(mut commands: Commands, asset_server: Res<AssetServer>) {
         }),
         FpsText,
     ));
+
+    // Health and armor
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Medium.ttf"),
+                font_size: 30.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(10.0),
+                right: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+        HealthText,
+    ));
+
+    // Ammunition of the weapon in hand
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Medium.ttf"),
+                font_size: 30.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(45.0),
+                right: Val::Px(10.0),
+                ..default()
+            },
+            ..default()
+        }),
+        AmmoText,
+    ));
+
+    // Match clock
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Medium.ttf"),
+                font_size: 30.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(10.0),
+                left: Val::Percent(47.0),
+                ..default()
+            },
+            ..default()
+        }),
+        MatchTimerText,
+    ));
+
+    // "Match starting in N", only during the countdown
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Medium.ttf"),
+                font_size: 50.0,
+                color: Color::YELLOW,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Percent(30.0),
+                left: Val::Percent(35.0),
+                ..default()
+            },
+            ..default()
+        }),
+        CountdownOverlay,
+    ));
+
+    // Direction of the last hit taken
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: asset_server.load("fonts/FiraSans-Medium.ttf"),
+                font_size: 24.0,
+                color: Color::RED,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Percent(60.0),
+                left: Val::Percent(45.0),
+                ..default()
+            },
+            ..default()
+        }),
+        DamageIndicator,
+    ));
 }
 
 struct UiAdvertisement {
@@ -163,6 +318,24 @@ pub struct FpsText;
 #[derive(Component)]
 pub struct MinimapPlayerMarker;
 
+#[derive(Component)]
+pub struct HealthText;
+
+#[derive(Component)]
+pub struct DamageIndicator;
+
+#[derive(Component)]
+pub struct AmmoText;
+
+#[derive(Component)]
+pub struct MatchTimerText;
+
+#[derive(Component)]
+pub struct CountdownOverlay;
+
+/// How long the damage direction stays on screen, in seconds.
+const DAMAGE_INDICATOR_TIME: f32 = 1.5;
+
 
 pub fn update_minimap(
     game_state: Res<GameState>,
@@ -204,30 +377,33 @@ if (elapsed % 0.5) < 0.016 {
                     }
                 }
 
-                // Draw player marker
-                if let Some(player_id) = &game_state.player_id {
-                    if let Some(&(player_x, player_y, _, _)) = game_state.players.get(player_id) {
-                        let marker_x = player_x * cell_size;
-                        let marker_y = player_y * cell_size;
+                // Draw our marker and our teammates', in the team colour
+                let shown = game_state.players.iter().filter(|(&id, snapshot)| {
+                    Some(id) == game_state.player_id || (snapshot.is_alive && game_state.is_teammate(id))
+                });
+                for (&id, snapshot) in shown {
+                    let marker_x = snapshot.x * cell_size;
+                    let marker_y = snapshot.y * cell_size;
+                    let size = if Some(id) == game_state.player_id { 5.0 } else { 4.0 };
+                    let color = game_state.team_of(id).map_or(Color::RED, team_color);
 
-                        parent.spawn((
-                            NodeBundle {
-                                style: Style {
-                                    position_type: PositionType::Absolute,
-                                    size: Size::new(Val::Px(5.0), Val::Px(5.0)),
-                                    position: UiRect {
-                                        left: Val::Px(marker_x),
-                                        bottom: Val::Px(marker_y),
-                                        ..default()
-                                    },
+                    parent.spawn((
+                        NodeBundle {
+                            style: Style {
+                                position_type: PositionType::Absolute,
+                                size: Size::new(Val::Px(size), Val::Px(size)),
+                                position: UiRect {
+                                    left: Val::Px(marker_x),
+                                    bottom: Val::Px(marker_y),
                                     ..default()
                                 },
-                                background_color: Color::RED.into(),
                                 ..default()
                             },
-                            MinimapPlayerMarker,
-                        ));
-                    }
+                            background_color: color.into(),
+                            ..default()
+                        },
+                        MinimapPlayerMarker,
+                    ));
                 }
             });
         }
@@ -235,6 +411,86 @@ if (elapsed % 0.5) < 0.016 {
 }
 }
 
+pub fn update_health_hud(
+    game_state: Res<GameState>,
+    player_rotation: Res<PlayerRotation>,
+    time: Res<Time>,
+    mut health_query: Query<&mut Text, (With<HealthText>, Without<DamageIndicator>)>,
+    mut indicator_query: Query<&mut Text, With<DamageIndicator>>,
+) {
+    for mut text in health_query.iter_mut() {
+        text.sections[0].value = format!("Health: {}  Armor: {}", game_state.health, game_state.armor);
+        text.sections[0].style.color = if game_state.health <= 25 { Color::RED } else { Color::WHITE };
+    }
+
+    let mut indicator = String::new();
+    let position = game_state.player_id.and_then(|id| game_state.players.get(&id));
+    if let (Some((hit_time, from)), Some(position)) = (game_state.last_damage, position) {
+        if time.elapsed_seconds() - hit_time < DAMAGE_INDICATOR_TIME {
+            // Même repère que les déplacements : W avance vers -z quand yaw = 0
+            let yaw = player_rotation.yaw;
+            let to_shooter = (from.0 - position.x, from.1 - position.y);
+            let ahead = -to_shooter.0 * yaw.sin() - to_shooter.1 * yaw.cos();
+            let right = to_shooter.0 * yaw.cos() - to_shooter.1 * yaw.sin();
+            let side = if ahead.abs() >= right.abs() {
+                if ahead > 0.0 { "front" } else { "behind" }
+            } else if right > 0.0 {
+                "the right"
+            } else {
+                "the left"
+            };
+            indicator = format!("Hit from {}", side);
+        }
+    }
+    for mut text in indicator_query.iter_mut() {
+        text.sections[0].value.clone_from(&indicator);
+    }
+}
+
+pub fn update_ammo_hud(
+    mut game_state: ResMut<GameState>,
+    time: Res<Time>,
+    mut query: Query<&mut Text, With<AmmoText>>,
+) {
+    let status = game_state.loadout.status(time.elapsed_seconds_f64());
+    let name = weapon(status.weapon).map_or("", |weapon| weapon.name);
+    let value = if status.reload_ms > 0 {
+        format!("{}  reloading...", name)
+    } else {
+        format!("{}  {} / {}", name, status.magazine, status.reserve)
+    };
+    for mut text in query.iter_mut() {
+        text.sections[0].value.clone_from(&value);
+        text.sections[0].style.color = if status.magazine == 0 { Color::RED } else { Color::WHITE };
+    }
+}
+
+pub fn update_match_hud(
+    game_state: Res<GameState>,
+    time: Res<Time>,
+    mut timer_query: Query<&mut Text, (With<MatchTimerText>, Without<CountdownOverlay>)>,
+    mut overlay_query: Query<&mut Text, With<CountdownOverlay>>,
+) {
+    let remaining = game_state.phase_ends_at.map(|ends_at| (ends_at - time.elapsed_seconds_f64()).max(0.0).ceil() as u64);
+    let (timer, overlay) = match (game_state.phase, remaining) {
+        (MatchPhase::WaitingForPlayers, _) => ("Waiting for players...".to_string(), String::new()),
+        (MatchPhase::Countdown, Some(secs)) => (String::new(), format!("Match starting in {}", secs)),
+        (MatchPhase::InProgress, Some(secs)) => (format!("{}:{:02}", secs / 60, secs % 60), String::new()),
+        (MatchPhase::PostMatch, Some(secs)) => (format!("Next match in {}", secs), String::new()),
+        // Pas encore de snapshot depuis le changement de phase
+        (_, None) => (String::new(), String::new()),
+    };
+    for mut text in timer_query.iter_mut() {
+        text.sections[0].value.clone_from(&timer);
+        // Dernières secondes du match en rouge
+        let ending = game_state.phase == MatchPhase::InProgress && remaining.is_some_and(|secs| secs <= 10);
+        text.sections[0].style.color = if ending { Color::RED } else { Color::WHITE };
+    }
+    for mut text in overlay_query.iter_mut() {
+        text.sections[0].value.clone_from(&overlay);
+    }
+}
+
 pub fn update_fps_text(
     diagnostics: Res<Diagnostics>,
     mut query: Query<&mut Text, With<FpsText>>,
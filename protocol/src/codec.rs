@@ -0,0 +1,231 @@
+//! Wire format shared by both ends.
+//!
+//! Every datagram starts with a fixed header: the 4-byte `MAGIC`, the
+//! little-endian `PROTOCOL_VERSION` of the sender and a one-byte message
+//! kind. That prefix and the `KIND_REJECTED` packet must stay the same across
+//! protocol versions so that mismatched peers can still tell each other what
+//! went wrong.
+//!
+//! The prefix is followed by the `SequenceHeader` used by the reliability
+//! layer (flags, sequence, ack, ack bitfield, then the reliable message id
+//! for reliable packets and the session token once the client has one), then
+//! by the bincode-encoded message.
+
+use std::fmt;
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use crate::player::SessionToken;
+use crate::MAX_PACKET_SIZE;
+
+pub const MAGIC: [u8; 4] = *b"MZWR";
+pub const PROTOCOL_VERSION: u16 = 20;
+pub const HEADER_SIZE: usize = 7;
+const SEQUENCE_HEADER_SIZE: usize = 9;
+const FLAG_RELIABLE: u8 = 1;
+const FLAG_HAS_ACK: u8 = 2;
+const FLAG_HAS_SESSION: u8 = 4;
+
+/// Kind of the packet the server answers an incompatible `Join` with. Its
+/// body is the plain UTF-8 reason rather than a bincode message.
+pub const KIND_REJECTED: u8 = 0xFF;
+
+/// Kind of a packet that only carries acks and has no message body.
+pub const KIND_ACK: u8 = 0xFE;
+
+pub trait Message: Serialize + DeserializeOwned {
+    fn kind(&self) -> u8;
+    /// Reliable messages are resent until the peer acks them.
+    fn is_reliable(&self) -> bool;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketHeader {
+    pub version: u16,
+    pub kind: u8,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SequenceHeader {
+    pub sequence: u16,
+    pub ack: Option<u16>, // most recent sequence received from the peer
+    pub ack_bits: u32,    // bit n set: `ack - n - 1` was received too
+    pub reliable_id: Option<u16>,
+    pub session: Option<SessionToken>, // set on every client packet after `Welcome`
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Packet<M> {
+    pub header: SequenceHeader,
+    pub message: Option<M>, // None for ack-only packets
+}
+
+#[derive(Debug)]
+pub enum CodecError {
+    Truncated,
+    BadMagic,
+    VersionMismatch { ours: u16, theirs: u16 },
+    KindMismatch { header: u8, body: u8 },
+    Rejected(String),
+    Body(bincode::Error),
+}
+
+impl fmt::Display for CodecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CodecError::Truncated => write!(f, "packet shorter than the header"),
+            CodecError::BadMagic => write!(f, "packet does not start with the protocol magic"),
+            CodecError::VersionMismatch { ours, theirs } => {
+                write!(f, "protocol version mismatch: we speak v{}, peer speaks v{}", ours, theirs)
+            }
+            CodecError::KindMismatch { header, body } => {
+                write!(f, "header announces kind {} but body is kind {}", header, body)
+            }
+            CodecError::Rejected(reason) => write!(f, "rejected by server: {}", reason),
+            CodecError::Body(e) => write!(f, "malformed message body: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for CodecError {}
+
+impl From<bincode::Error> for CodecError {
+    fn from(e: bincode::Error) -> Self {
+        CodecError::Body(e)
+    }
+}
+
+fn options() -> impl Options {
+    bincode::DefaultOptions::new().with_limit(MAX_PACKET_SIZE as u64)
+}
+
+fn write_header(buf: &mut Vec<u8>, kind: u8) {
+    buf.extend_from_slice(&MAGIC);
+    buf.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    buf.push(kind);
+}
+
+fn write_sequence_header(buf: &mut Vec<u8>, header: &SequenceHeader) {
+    let mut flags = 0;
+    if header.reliable_id.is_some() {
+        flags |= FLAG_RELIABLE;
+    }
+    if header.ack.is_some() {
+        flags |= FLAG_HAS_ACK;
+    }
+    if header.session.is_some() {
+        flags |= FLAG_HAS_SESSION;
+    }
+    buf.push(flags);
+    buf.extend_from_slice(&header.sequence.to_le_bytes());
+    buf.extend_from_slice(&header.ack.unwrap_or(0).to_le_bytes());
+    buf.extend_from_slice(&header.ack_bits.to_le_bytes());
+    if let Some(id) = header.reliable_id {
+        buf.extend_from_slice(&id.to_le_bytes());
+    }
+    if let Some(session) = header.session {
+        buf.extend_from_slice(&session.to_le_bytes());
+    }
+}
+
+/// Returns the sequence header and the number of bytes it took.
+fn read_sequence_header(bytes: &[u8]) -> Result<(SequenceHeader, usize), CodecError> {
+    if bytes.len() < SEQUENCE_HEADER_SIZE {
+        return Err(CodecError::Truncated);
+    }
+    let flags = bytes[0];
+    let mut header = SequenceHeader {
+        sequence: u16::from_le_bytes([bytes[1], bytes[2]]),
+        ack: (flags & FLAG_HAS_ACK != 0).then(|| u16::from_le_bytes([bytes[3], bytes[4]])),
+        ack_bits: u32::from_le_bytes([bytes[5], bytes[6], bytes[7], bytes[8]]),
+        reliable_id: None,
+        session: None,
+    };
+    let mut len = SEQUENCE_HEADER_SIZE;
+    if flags & FLAG_RELIABLE != 0 {
+        let id = bytes.get(len..len + 2).ok_or(CodecError::Truncated)?;
+        header.reliable_id = Some(u16::from_le_bytes([id[0], id[1]]));
+        len += 2;
+    }
+    if flags & FLAG_HAS_SESSION != 0 {
+        let session = bytes.get(len..len + 8).ok_or(CodecError::Truncated)?;
+        header.session = Some(SessionToken::from_le_bytes(session.try_into().unwrap()));
+        len += 8;
+    }
+    Ok((header, len))
+}
+
+/// Both headers of a current-version packet, without decoding its body.
+/// Lets the server find out who sent a packet before handing it to an
+/// `Endpoint`.
+pub fn read_headers(bytes: &[u8]) -> Result<(PacketHeader, SequenceHeader), CodecError> {
+    let (prefix, header, _) = read_headers_and_len(bytes)?;
+    Ok((prefix, header))
+}
+
+fn read_headers_and_len(bytes: &[u8]) -> Result<(PacketHeader, SequenceHeader, usize), CodecError> {
+    let prefix = read_header(bytes)?;
+    if prefix.kind == KIND_REJECTED {
+        return Err(CodecError::Rejected(String::from_utf8_lossy(&bytes[HEADER_SIZE..]).into_owned()));
+    }
+    if prefix.version != PROTOCOL_VERSION {
+        return Err(CodecError::VersionMismatch { ours: PROTOCOL_VERSION, theirs: prefix.version });
+    }
+    let (header, header_len) = read_sequence_header(&bytes[HEADER_SIZE..])?;
+    Ok((prefix, header, HEADER_SIZE + header_len))
+}
+
+/// Reads the header without checking the version, so callers can react to
+/// a peer running another protocol version.
+pub fn read_header(bytes: &[u8]) -> Result<PacketHeader, CodecError> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(CodecError::Truncated);
+    }
+    if bytes[..4] != MAGIC {
+        return Err(CodecError::BadMagic);
+    }
+    Ok(PacketHeader {
+        version: u16::from_le_bytes([bytes[4], bytes[5]]),
+        kind: bytes[6],
+    })
+}
+
+pub(crate) fn encode_body<M: Message>(message: &M) -> Result<Vec<u8>, CodecError> {
+    Ok(options().serialize(message)?)
+}
+
+pub(crate) fn write_packet(kind: u8, header: &SequenceHeader, body: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_SIZE + SEQUENCE_HEADER_SIZE + 10 + body.len());
+    write_header(&mut buf, kind);
+    write_sequence_header(&mut buf, header);
+    buf.extend_from_slice(body);
+    buf
+}
+
+pub fn encode<M: Message>(header: &SequenceHeader, message: &M) -> Result<Vec<u8>, CodecError> {
+    Ok(write_packet(message.kind(), header, &encode_body(message)?))
+}
+
+pub fn encode_ack(header: &SequenceHeader) -> Vec<u8> {
+    write_packet(KIND_ACK, header, &[])
+}
+
+pub fn decode<M: Message>(bytes: &[u8]) -> Result<Packet<M>, CodecError> {
+    let (prefix, header, len) = read_headers_and_len(bytes)?;
+    let body = &bytes[len..];
+    if prefix.kind == KIND_ACK {
+        return Ok(Packet { header, message: None });
+    }
+    let message: M = options().deserialize(body)?;
+    if message.kind() != prefix.kind {
+        return Err(CodecError::KindMismatch { header: prefix.kind, body: message.kind() });
+    }
+    Ok(Packet { header, message: Some(message) })
+}
+
+pub fn encode_rejection(reason: &str) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(HEADER_SIZE + reason.len());
+    write_header(&mut buf, KIND_REJECTED);
+    buf.extend_from_slice(reason.as_bytes());
+    buf
+}
@@ -0,0 +1,64 @@
+//! How much a shot hurts and how health and armor soak it up.
+//!
+//! Shots are traced on the map from above, the pitch of the shooter's camera
+//! only decides which part of the target the bullet reaches: over the head
+//! or into the floor is a miss, above `HEAD_HEIGHT` a headshot.
+
+use serde::{Serialize, Deserialize};
+use crate::weapon::WeaponDef;
+
+pub const MAX_HEALTH: u32 = 100;
+pub const SPAWN_ARMOR: u32 = 50;
+/// Share of each hit that armor takes instead of health, while it lasts.
+pub const ARMOR_ABSORPTION: f32 = 0.5;
+
+pub const HEADSHOT_MULTIPLIER: f32 = 2.0;
+/// Shots deal full damage up to the weapon's `falloff_start`, then less and
+/// less down to this share of it at the weapon's range.
+pub const MIN_FALLOFF: f32 = 0.5;
+
+pub const EYE_HEIGHT: f32 = 1.6; // where the camera sits, and shots start
+pub const PLAYER_HEIGHT: f32 = 1.8;
+pub const HEAD_HEIGHT: f32 = 1.45;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum HitZone {
+    Body,
+    Head,
+}
+
+/// The part of a player `distance` away that a shot fired with `pitch`
+/// (radians, positive looking up) reaches, if any. A non-finite pitch misses.
+pub fn hit_zone(distance: f32, pitch: f32) -> Option<HitZone> {
+    if !pitch.is_finite() {
+        return None;
+    }
+    // Un tangage de ±90° n'a pas de sens, et tan() exploserait
+    let pitch = pitch.clamp(-1.5, 1.5);
+    let height = EYE_HEIGHT + distance * pitch.tan();
+    if !(0.0..=PLAYER_HEIGHT).contains(&height) {
+        None
+    } else if height >= HEAD_HEIGHT {
+        Some(HitZone::Head)
+    } else {
+        Some(HitZone::Body)
+    }
+}
+
+/// Damage of a hit with `weapon` on `zone` from `distance` away, before armor.
+pub fn shot_damage(weapon: &WeaponDef, distance: f32, zone: HitZone) -> u32 {
+    let falloff_range = (weapon.range - weapon.falloff_start).max(f32::EPSILON);
+    let progress = ((distance - weapon.falloff_start) / falloff_range).clamp(0.0, 1.0);
+    let falloff = 1.0 - progress * (1.0 - MIN_FALLOFF);
+    let multiplier = match zone {
+        HitZone::Body => 1.0,
+        HitZone::Head => HEADSHOT_MULTIPLIER,
+    };
+    (weapon.damage * multiplier * falloff).round() as u32
+}
+
+/// Health and armor left after taking `damage`.
+pub fn apply_damage(health: u32, armor: u32, damage: u32) -> (u32, u32) {
+    let absorbed = ((damage as f32 * ARMOR_ABSORPTION) as u32).min(armor);
+    (health.saturating_sub(damage - absorbed), armor - absorbed)
+}
@@ -0,0 +1,25 @@
+//! Types shared by the Maze Wars server and client: the messages exchanged
+//! over UDP, the map layout and the gameplay constants both sides rely on.
+
+pub mod codec;
+pub mod damage;
+pub mod map;
+pub mod map_transfer;
+pub mod messages;
+pub mod movement;
+pub mod phase;
+pub mod player;
+pub mod reliability;
+pub mod scoreboard;
+pub mod snapshot;
+pub mod weapon;
+
+pub use map::Map;
+pub use messages::{ClientMessage, ServerMessage};
+pub use player::{PlayerId, PlayerSnapshot, SessionToken, TeamId};
+
+pub const DEFAULT_PORT: u16 = 34254;
+pub const MAX_PACKET_SIZE: usize = 4096;
+
+/// Clients send a `Heartbeat` when they have sent nothing else for this long.
+pub const HEARTBEAT_INTERVAL: std::time::Duration = std::time::Duration::from_secs(1);
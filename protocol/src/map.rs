@@ -0,0 +1,98 @@
+use serde::{Serialize, Deserialize};
+
+pub const MAP_WIDTH: usize = 25;
+pub const MAP_HEIGHT: usize = 25;
+pub const PLAYER_SIZE: f32 = 0.5; // Taille du joueur
+
+/// Where a ray first enters a wall cell.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WallHit {
+    pub point: (f32, f32),
+    pub distance: f32,
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
+pub struct Map {
+    pub cells: Vec<Vec<bool>>, // true pour un mur, false pour un espace vide
+    pub internal_wall_count: usize,
+    pub map_width: usize,
+    pub map_height: usize,
+}
+
+impl Map {
+    pub fn from_cells(cells: Vec<Vec<bool>>, internal_wall_count: usize) -> Self {
+        let map_height = cells.len();
+        let map_width = cells.first().map_or(0, |row| row.len());
+        Map { cells, internal_wall_count, map_width, map_height }
+    }
+
+    pub fn is_wall(&self, x: usize, y: usize) -> bool {
+        self.cells[y][x]
+    }
+
+    /// Walks the grid cell by cell (DDA) from `origin` along `direction` and
+    /// returns the first wall crossed within `max_distance`. Cell `(x, y)` is
+    /// the unit square centred on `(x, y)`, the way the client draws it.
+    pub fn raycast(&self, origin: (f32, f32), direction: (f32, f32), max_distance: f32) -> Option<WallHit> {
+        let length = (direction.0 * direction.0 + direction.1 * direction.1).sqrt();
+        if length == 0.0 {
+            return None;
+        }
+        let (dir_x, dir_y) = (direction.0 / length, direction.1 / length);
+
+        // En coordonnées de grille, la case i couvre [i, i + 1)
+        let (grid_x, grid_y) = (origin.0 + 0.5, origin.1 + 0.5);
+        let (mut cell_x, mut cell_y) = (grid_x.floor() as i64, grid_y.floor() as i64);
+        let (step_x, step_y) = (if dir_x > 0.0 { 1 } else { -1 }, if dir_y > 0.0 { 1 } else { -1 });
+        let delta_x = if dir_x == 0.0 { f32::INFINITY } else { (1.0 / dir_x).abs() };
+        let delta_y = if dir_y == 0.0 { f32::INFINITY } else { (1.0 / dir_y).abs() };
+        let mut side_x = if dir_x > 0.0 { (cell_x as f32 + 1.0 - grid_x) * delta_x } else { (grid_x - cell_x as f32) * delta_x };
+        let mut side_y = if dir_y > 0.0 { (cell_y as f32 + 1.0 - grid_y) * delta_y } else { (grid_y - cell_y as f32) * delta_y };
+        let mut distance = 0.0;
+
+        while distance <= max_distance {
+            if cell_x < 0 || cell_y < 0 || cell_x as usize >= self.map_width || cell_y as usize >= self.map_height {
+                return None;
+            }
+            if self.is_wall(cell_x as usize, cell_y as usize) {
+                return Some(WallHit {
+                    point: (origin.0 + dir_x * distance, origin.1 + dir_y * distance),
+                    distance,
+                });
+            }
+            if side_x < side_y {
+                distance = side_x;
+                side_x += delta_x;
+                cell_x += step_x;
+            } else {
+                distance = side_y;
+                side_y += delta_y;
+                cell_y += step_y;
+            }
+        }
+        None
+    }
+}
+
+/// Whether a player centred on `(x, y)` stays clear of every wall. Cells
+/// are centred on their coordinates like in `Map::raycast`, and a player
+/// touching a wall without entering it is fine. Outside the map counts as
+/// wall.
+pub fn is_valid_move(map: &Map, x: f32, y: f32) -> bool {
+    let half_size = PLAYER_SIZE / 2.0;
+    // La case i couvre [i - 0.5, i + 0.5)
+    let cells = |low: f32, high: f32| ((low + 0.5).floor() as i64, (high + 0.5).ceil() as i64 - 1);
+    let (start_x, end_x) = cells(x - half_size, x + half_size);
+    let (start_y, end_y) = cells(y - half_size, y + half_size);
+
+    for yy in start_y..=end_y {
+        for xx in start_x..=end_x {
+            let outside = xx < 0 || yy < 0 || xx as usize >= map.map_width || yy as usize >= map.map_height;
+            if outside || map.is_wall(xx as usize, yy as usize) {
+                return false;
+            }
+        }
+    }
+
+    true
+}
@@ -0,0 +1,128 @@
+//! Sending a `Map` over several datagrams.
+//!
+//! The cells are run-length encoded: alternating runs of floor and wall
+//! cells, row by row, starting with floor, each length written as a LEB128
+//! varint. The encoded bytes are cut into `MAP_CHUNK_SIZE` chunks, sent as
+//! separate `MapChunk` messages and put back together by a `MapAssembler`.
+
+use serde::{Serialize, Deserialize};
+use crate::map::Map;
+
+/// Keeps every chunk well below `MAX_PACKET_SIZE` once headers are added.
+pub const MAP_CHUNK_SIZE: usize = 1024;
+
+/// Largest map a client accepts, so a bogus header cannot make it allocate
+/// gigabytes.
+pub const MAX_MAP_CELLS: usize = 1 << 20;
+
+/// Sent with every chunk so they can be reassembled in any order.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MapInfo {
+    pub id: u32, // changes every time the server generates a new map
+    pub width: u16,
+    pub height: u16,
+    pub internal_wall_count: u32,
+    pub chunk_count: u16,
+}
+
+pub fn compress(cells: &[Vec<bool>]) -> Vec<u8> {
+    let mut data = Vec::new();
+    let mut current = false;
+    let mut run = 0u64;
+    for &cell in cells.iter().flatten() {
+        if cell != current {
+            write_varint(&mut data, run);
+            current = cell;
+            run = 0;
+        }
+        run += 1;
+    }
+    write_varint(&mut data, run);
+    data
+}
+
+/// Returns `None` unless `data` describes exactly `width * height` cells.
+pub fn decompress(data: &[u8], width: usize, height: usize) -> Option<Vec<Vec<bool>>> {
+    let total = width.checked_mul(height).filter(|&total| total <= MAX_MAP_CELLS)?;
+    let mut flat = Vec::with_capacity(total);
+    let mut current = false;
+    let mut rest = data;
+    while !rest.is_empty() {
+        let (run, len) = read_varint(rest)?;
+        rest = &rest[len..];
+        if run > (total - flat.len()) as u64 {
+            return None;
+        }
+        flat.extend(std::iter::repeat_n(current, run as usize));
+        current = !current;
+    }
+    if flat.len() != total {
+        return None;
+    }
+    Some(flat.chunks(width.max(1)).map(|row| row.to_vec()).collect())
+}
+
+/// Compresses `map` and cuts it into the chunks to send, in order.
+pub fn split(map: &Map, id: u32) -> (MapInfo, Vec<Vec<u8>>) {
+    let data = compress(&map.cells);
+    let chunks: Vec<Vec<u8>> = data.chunks(MAP_CHUNK_SIZE).map(|chunk| chunk.to_vec()).collect();
+    let info = MapInfo {
+        id,
+        width: map.map_width as u16,
+        height: map.map_height as u16,
+        internal_wall_count: map.internal_wall_count as u32,
+        chunk_count: chunks.len() as u16,
+    };
+    (info, chunks)
+}
+
+/// Collects the chunks of one map until all of them arrived.
+pub struct MapAssembler {
+    info: MapInfo,
+    chunks: Vec<Option<Vec<u8>>>,
+}
+
+impl MapAssembler {
+    pub fn new(info: MapInfo) -> Self {
+        Self { info, chunks: vec![None; info.chunk_count as usize] }
+    }
+
+    pub fn info(&self) -> MapInfo {
+        self.info
+    }
+
+    /// Stores one chunk and returns the map once the last one is in. Chunks
+    /// of another map, out of range or that do not decode are ignored.
+    pub fn add(&mut self, info: MapInfo, index: u16, data: Vec<u8>) -> Option<Map> {
+        if info != self.info {
+            return None;
+        }
+        *self.chunks.get_mut(index as usize)? = Some(data);
+        if self.chunks.iter().any(Option::is_none) {
+            return None;
+        }
+        let data: Vec<u8> = self.chunks.iter().flatten().flatten().copied().collect();
+        let cells = decompress(&data, self.info.width as usize, self.info.height as usize)?;
+        Some(Map::from_cells(cells, self.info.internal_wall_count as usize))
+    }
+}
+
+fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buf.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buf.push(value as u8);
+}
+
+/// Returns the value and the number of bytes it took.
+fn read_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (i, &byte) in bytes.iter().enumerate().take(10) {
+        value |= ((byte & 0x7F) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
@@ -0,0 +1,103 @@
+use serde::{Serialize, Deserialize};
+use crate::codec::Message;
+use crate::damage::HitZone;
+use crate::map_transfer::MapInfo;
+use crate::phase::{MatchClock, MatchPhase};
+use crate::scoreboard::{MatchWinner, Scoreboard};
+use crate::player::{PlayerId, SessionToken, TeamId};
+use crate::snapshot::PlayerDelta;
+use crate::weapon::{AmmoStatus, WeaponId};
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ClientMessage {
+    Join { name: String },
+    Move { sequence: u32, direction: (f32, f32) }, // sequence starts at 1 and increases by one per input
+    // `pitch` of the camera decides where the target is hit (see `damage`),
+    // `view_tick` is the server tick the shooter was looking at. Switching
+    // weapons needs no message, the next shot says which one is in hand
+    Shoot { weapon: WeaponId, direction: (f32, f32), pitch: f32, view_tick: u64 },
+    Heartbeat, // keeps an idle client from timing out
+    Leave,
+    Reconnect { session: SessionToken, name: String }, // name is used for a fresh Join when the session expired
+    MapReceived { map_id: u32 }, // every chunk of the map arrived and was decoded
+    Reload { weapon: WeaponId }, // the weapon to put in hand and reload
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum ServerMessage {
+    Welcome { player_id: PlayerId, name: String, session: SessionToken, difficulty: u8 }, // name may differ from the one sent in Join
+    // Changes since the `baseline` tick the recipient acknowledged, or everything when
+    // `baseline` is None (see `snapshot`). `last_input` is the last Move applied for the recipient
+    // and `ammo` what the recipient has left in the weapon in hand.
+    GameState { tick: u64, baseline: Option<u64>, players: Vec<PlayerDelta>, removed: Vec<PlayerId>, last_input: u32, ammo: AmmoStatus, clock: MatchClock },
+    // Sent to the target and the shooter. `from` is where the shooter stood, `health`
+    // and `armor` what the target has left
+    PlayerShot { shooter: PlayerId, target: PlayerId, zone: HitZone, damage: u32, health: u32, armor: u32, from: (f32, f32) },
+    PlayerDied { player: PlayerId },
+    ShotFired { shooter: PlayerId, impact: (f32, f32), hit_wall: bool }, // where the shot stopped
+    PlayerLeft { player: PlayerId }, // left the game or timed out
+    GameOver { winner: MatchWinner, scoreboard: Scoreboard },
+    PlayerJoined { player: PlayerId, name: String, team: Option<TeamId> }, // sent for every player already in the game too
+    MapChunk { info: MapInfo, index: u16, data: Vec<u8> }, // see `map_transfer`
+    PlayerRespawned { player: PlayerId, position: (f32, f32) }, // alive again after `PlayerDied`
+    PhaseChanged { phase: MatchPhase }, // also sent after `Welcome`
+    PlayerTeam { player: PlayerId, team: Option<TeamId> }, // moved to another team to even them out
+}
+
+impl ClientMessage {
+    // `Join` must keep kind 0 in every protocol version: it is how the server
+    // recognises a handshake it has to reject.
+    pub const KIND_JOIN: u8 = 0;
+    pub const KIND_MOVE: u8 = 1;
+    pub const KIND_SHOOT: u8 = 2;
+    pub const KIND_HEARTBEAT: u8 = 3;
+    pub const KIND_LEAVE: u8 = 4;
+    pub const KIND_RECONNECT: u8 = 5;
+    pub const KIND_MAP_RECEIVED: u8 = 6;
+    pub const KIND_RELOAD: u8 = 7;
+}
+
+impl Message for ClientMessage {
+    fn kind(&self) -> u8 {
+        match self {
+            ClientMessage::Join { .. } => Self::KIND_JOIN,
+            ClientMessage::Move { .. } => Self::KIND_MOVE,
+            ClientMessage::Shoot { .. } => Self::KIND_SHOOT,
+            ClientMessage::Heartbeat => Self::KIND_HEARTBEAT,
+            ClientMessage::Leave => Self::KIND_LEAVE,
+            ClientMessage::Reconnect { .. } => Self::KIND_RECONNECT,
+            ClientMessage::MapReceived { .. } => Self::KIND_MAP_RECEIVED,
+            ClientMessage::Reload { .. } => Self::KIND_RELOAD,
+        }
+    }
+
+    fn is_reliable(&self) -> bool {
+        // A lost move is superseded by the next one, a lost heartbeat by any packet
+        !matches!(self, ClientMessage::Move { .. } | ClientMessage::Heartbeat)
+    }
+}
+
+impl Message for ServerMessage {
+    fn kind(&self) -> u8 {
+        match self {
+            ServerMessage::Welcome { .. } => 0,
+            ServerMessage::GameState { .. } => 1,
+            ServerMessage::PlayerShot { .. } => 2,
+            ServerMessage::PlayerDied { .. } => 3,
+            ServerMessage::GameOver { .. } => 4,
+            ServerMessage::ShotFired { .. } => 5,
+            ServerMessage::PlayerLeft { .. } => 6,
+            ServerMessage::PlayerJoined { .. } => 7,
+            ServerMessage::MapChunk { .. } => 8,
+            ServerMessage::PlayerRespawned { .. } => 9,
+            ServerMessage::PhaseChanged { .. } => 10,
+            ServerMessage::PlayerTeam { .. } => 11,
+        }
+    }
+
+    fn is_reliable(&self) -> bool {
+        // Snapshots are sent every tick, only the latest one matters, and
+        // shot impacts are purely cosmetic
+        !matches!(self, ServerMessage::GameState { .. } | ServerMessage::ShotFired { .. })
+    }
+}
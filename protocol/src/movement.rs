@@ -0,0 +1,56 @@
+use crate::map::{is_valid_move, Map, PLAYER_SIZE};
+use crate::player::PLAYER_SPEED;
+
+/// Left between a blocked player and what blocks it, so rounding never
+/// leaves them overlapping.
+const CONTACT_GAP: f32 = 1e-3;
+
+/// One `Move` input step. The server and the client's prediction both go
+/// through this so they always agree on where a player ends up.
+///
+/// Each axis is resolved on its own, x then y: a player running diagonally
+/// into a wall keeps sliding along it, and a blocked axis stops flush
+/// against the wall or player in the way. `others` are the positions of the
+/// players that block this one.
+pub fn apply_move(map: &Map, position: (f32, f32), direction: (f32, f32), others: &[(f32, f32)]) -> (f32, f32) {
+    // Des joueurs qui se chevauchent déjà (apparition au même endroit) doivent pouvoir s'écarter
+    let others: Vec<(f32, f32)> = others.iter().copied().filter(|&other| !players_overlap(position, other)).collect();
+    let position = move_along(map, position, direction.0 * PLAYER_SPEED, false, &others);
+    move_along(map, position, direction.1 * PLAYER_SPEED, true, &others)
+}
+
+pub fn players_overlap(a: (f32, f32), b: (f32, f32)) -> bool {
+    (a.0 - b.0).abs() < PLAYER_SIZE && (a.1 - b.1).abs() < PLAYER_SIZE
+}
+
+fn is_free(map: &Map, position: (f32, f32), others: &[(f32, f32)]) -> bool {
+    is_valid_move(map, position.0, position.1) && !others.iter().any(|&other| players_overlap(position, other))
+}
+
+/// Moves `step` along one axis, or as far as possible when blocked.
+fn move_along(map: &Map, position: (f32, f32), step: f32, vertical: bool, others: &[(f32, f32)]) -> (f32, f32) {
+    let at = |value: f32| if vertical { (position.0, value) } else { (value, position.1) };
+    let axis = |point: (f32, f32)| if vertical { point.1 } else { point.0 };
+    let start = axis(position);
+    if step == 0.0 || is_free(map, at(start + step), others) {
+        return at(start + step);
+    }
+
+    // Points de contact possibles : le bord de la case atteinte, et chaque joueur touché
+    let half_size = PLAYER_SIZE / 2.0;
+    let sign = step.signum();
+    let edge = start + step + sign * half_size;
+    let cell_boundary = (edge + 0.5).floor() - sign * 0.5;
+    let mut stops = vec![cell_boundary - sign * (half_size + CONTACT_GAP)];
+    stops.extend(
+        others
+            .iter()
+            .filter(|&&other| players_overlap(at(start + step), other))
+            .map(|&other| axis(other) - sign * (PLAYER_SIZE + CONTACT_GAP)),
+    );
+
+    // Le plus loin possible sans reculer ni traverser quoi que ce soit
+    stops.retain(|&stop| (stop - start) * sign >= 0.0 && (stop - start).abs() < step.abs());
+    stops.sort_by(|a, b| ((b - start) * sign).total_cmp(&((a - start) * sign)));
+    stops.into_iter().map(at).find(|&stop| is_free(map, stop, others)).unwrap_or(position)
+}
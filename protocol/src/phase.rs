@@ -0,0 +1,19 @@
+use serde::{Serialize, Deserialize};
+
+/// Where the server is in the life of a match. Phases follow each other in
+/// this order, then start over from `WaitingForPlayers`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MatchPhase {
+    #[default]
+    WaitingForPlayers, // fewer players than the server needs to start
+    Countdown, // enough players, the match starts soon
+    InProgress, // shots count, until the match time runs out
+    PostMatch, // scores are shown, then a new map is sent
+}
+
+/// The phase of the match and how long it has left, sent in every snapshot.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct MatchClock {
+    pub phase: MatchPhase,
+    pub remaining_ms: u32, // 0 while waiting for players, which has no end
+}
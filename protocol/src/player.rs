@@ -0,0 +1,27 @@
+use serde::{Serialize, Deserialize};
+
+pub const PLAYER_SPEED: f32 = 0.3; // distance covered by one Move
+/// Clients send at most one `Move` this often (seconds), which makes the
+/// top speed `PLAYER_SPEED / MOVE_INTERVAL`. The server enforces it.
+pub const MOVE_INTERVAL: f32 = 0.08;
+
+/// Assigned by the server at `Join` and used in every message that refers
+/// to a player. Names are only shown to people, several players may not
+/// share one.
+pub type PlayerId = u32;
+
+/// Index of a team, from 0, in game modes that have teams.
+pub type TeamId = u8;
+
+/// Secret handed out in `Welcome`. The client attaches it to every packet so
+/// the server can recognise the player after their address changed.
+pub type SessionToken = u64;
+
+/// What every client is told about a player on each `GameState` update.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, PartialEq)]
+pub struct PlayerSnapshot {
+    pub x: f32,
+    pub y: f32,
+    pub rotation: f32,
+    pub is_alive: bool,
+}
@@ -0,0 +1,286 @@
+//! Minimal reliability layer on top of UDP.
+//!
+//! Each side keeps one `Endpoint` per peer. Every packet gets a sequence
+//! number and acknowledges the last 33 packets received from the peer
+//! (`ack` plus the 32-bit `ack_bits` history). Reliable messages also get a
+//! reliable id and are re-sent with a fresh sequence number until one of
+//! their transmissions is acknowledged; the receiver uses the id to drop
+//! duplicates. Unreliable messages are never re-sent.
+
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use crate::codec::{self, CodecError, Message, SequenceHeader};
+use crate::player::SessionToken;
+
+pub const RESEND_AFTER: Duration = Duration::from_millis(150);
+pub const ACK_INTERVAL: Duration = Duration::from_millis(50);
+const DELIVERED_HISTORY: usize = 256;
+/// How many of our own most recent sequences `was_acked` can answer for.
+pub const ACKED_HISTORY: u16 = 256;
+
+struct PendingMessage {
+    reliable_id: u16,
+    kind: u8,
+    body: Vec<u8>,
+    sequences: Vec<u16>, // every sequence this message was sent with
+    last_sent: Instant,
+}
+
+#[derive(Debug)]
+pub struct Received<M> {
+    pub message: M,
+    pub sequence: u16,
+    /// False when a packet with a higher sequence was already received, so
+    /// latest-wins messages such as snapshots can be ignored.
+    pub is_latest: bool,
+}
+
+#[derive(Default)]
+pub struct Endpoint {
+    local_sequence: u16,
+    remote_sequence: Option<u16>,
+    received_bits: u32,
+    next_reliable_id: u16,
+    pending: Vec<PendingMessage>,
+    delivered_reliable: VecDeque<u16>,
+    ack_owed: bool,
+    last_sent: Option<Instant>,
+    last_received: Option<Instant>,
+    session: Option<SessionToken>,
+    acked: [u64; ACKED_HISTORY as usize / 64], // bit `sequence % ACKED_HISTORY`
+}
+
+/// `a` is more recent than `b`, accounting for wrap-around.
+pub fn sequence_greater_than(a: u16, b: u16) -> bool {
+    a != b && a.wrapping_sub(b) < u16::MAX / 2
+}
+
+impl Endpoint {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Attaches `session` to every packet sent from now on.
+    pub fn set_session(&mut self, session: SessionToken) {
+        self.session = Some(session);
+    }
+
+    pub fn session(&self) -> Option<SessionToken> {
+        self.session
+    }
+
+    /// Starts over as if talking to the peer for the first time, keeping
+    /// only the session token.
+    pub fn reset(&mut self) {
+        *self = Self { session: self.session, ..Self::default() };
+    }
+
+    fn next_header(&mut self, reliable_id: Option<u16>, now: Instant) -> SequenceHeader {
+        let header = SequenceHeader {
+            sequence: self.local_sequence,
+            ack: self.remote_sequence,
+            ack_bits: self.received_bits,
+            reliable_id,
+            session: self.session,
+        };
+        self.set_acked(header.sequence, false);
+        self.local_sequence = self.local_sequence.wrapping_add(1);
+        self.ack_owed = false;
+        self.last_sent = Some(now);
+        header
+    }
+
+    pub fn send<M: Message>(&mut self, message: &M, now: Instant) -> Result<Vec<u8>, CodecError> {
+        let body = codec::encode_body(message)?;
+        if !message.is_reliable() {
+            let header = self.next_header(None, now);
+            return Ok(codec::write_packet(message.kind(), &header, &body));
+        }
+
+        let reliable_id = self.next_reliable_id;
+        self.next_reliable_id = self.next_reliable_id.wrapping_add(1);
+        let header = self.next_header(Some(reliable_id), now);
+        let packet = codec::write_packet(message.kind(), &header, &body);
+        self.pending.push(PendingMessage {
+            reliable_id,
+            kind: message.kind(),
+            body,
+            sequences: vec![header.sequence],
+            last_sent: now,
+        });
+        Ok(packet)
+    }
+
+    /// Processes an incoming packet. Returns `None` for ack-only packets and
+    /// for duplicates or stale copies of messages already delivered.
+    pub fn receive<M: Message>(&mut self, bytes: &[u8], now: Instant) -> Result<Option<Received<M>>, CodecError> {
+        self.receive_if(bytes, now, |_| true)
+    }
+
+    /// Like `receive`, but a message seen for the first time that `accept`
+    /// refuses is dropped as if it was lost: it is not acknowledged, so the
+    /// peer cannot take it for delivered.
+    pub fn receive_if<M: Message>(&mut self, bytes: &[u8], now: Instant, accept: impl FnOnce(&M) -> bool) -> Result<Option<Received<M>>, CodecError> {
+        let packet = codec::decode::<M>(bytes)?;
+        self.last_received = Some(now);
+        let header = packet.header;
+        if let Some(ack) = header.ack {
+            self.process_acks(ack, header.ack_bits);
+        }
+        if let Some(message) = &packet.message {
+            if !self.was_received(header.sequence) && !accept(message) {
+                return Ok(None);
+            }
+        }
+        let (is_new, is_latest) = self.record_received(header.sequence);
+
+        let Some(message) = packet.message else {
+            return Ok(None);
+        };
+        self.ack_owed = true;
+
+        let deliver = match header.reliable_id {
+            Some(id) => {
+                if self.delivered_reliable.contains(&id) {
+                    false
+                } else {
+                    if self.delivered_reliable.len() == DELIVERED_HISTORY {
+                        self.delivered_reliable.pop_front();
+                    }
+                    self.delivered_reliable.push_back(id);
+                    true
+                }
+            }
+            None => is_new,
+        };
+
+        Ok(deliver.then_some(Received { message, sequence: header.sequence, is_latest }))
+    }
+
+    /// Re-encodes every reliable message that has gone unacknowledged for
+    /// `RESEND_AFTER`.
+    pub fn resend_due(&mut self, now: Instant) -> Vec<Vec<u8>> {
+        let mut packets = Vec::new();
+        for index in 0..self.pending.len() {
+            if now.duration_since(self.pending[index].last_sent) < RESEND_AFTER {
+                continue;
+            }
+            let header = self.next_header(Some(self.pending[index].reliable_id), now);
+            let pending = &mut self.pending[index];
+            pending.sequences.push(header.sequence);
+            pending.last_sent = now;
+            packets.push(codec::write_packet(pending.kind, &header, &pending.body));
+        }
+        packets
+    }
+
+    /// True when the peer sent us messages we have not acknowledged yet and
+    /// nothing went out for a while to carry the ack.
+    pub fn ack_due(&self, now: Instant) -> bool {
+        self.ack_owed && self.last_sent.is_none_or(|sent| now.duration_since(sent) >= ACK_INTERVAL)
+    }
+
+    /// Sequence the next packet sent will carry.
+    pub fn next_sequence(&self) -> u16 {
+        self.local_sequence
+    }
+
+    pub fn last_sent(&self) -> Option<Instant> {
+        self.last_sent
+    }
+
+    pub fn last_received(&self) -> Option<Instant> {
+        self.last_received
+    }
+
+    /// Whether the peer acknowledged our packet `sequence`. Always false for
+    /// sequences older than the last `ACKED_HISTORY` sent.
+    pub fn was_acked(&self, sequence: u16) -> bool {
+        let age = self.local_sequence.wrapping_sub(sequence);
+        let (word, mask) = Self::acked_slot(sequence);
+        (1..=ACKED_HISTORY).contains(&age) && self.acked[word] & mask != 0
+    }
+
+    fn acked_slot(sequence: u16) -> (usize, u64) {
+        let bit = sequence % ACKED_HISTORY;
+        ((bit / 64) as usize, 1 << (bit % 64))
+    }
+
+    fn set_acked(&mut self, sequence: u16, acked: bool) {
+        let (word, mask) = Self::acked_slot(sequence);
+        if acked {
+            self.acked[word] |= mask;
+        } else {
+            self.acked[word] &= !mask;
+        }
+    }
+
+    pub fn ack(&mut self, now: Instant) -> Vec<u8> {
+        let header = self.next_header(None, now);
+        codec::encode_ack(&header)
+    }
+
+    fn was_received(&self, sequence: u16) -> bool {
+        let Some(remote) = self.remote_sequence else {
+            return false;
+        };
+        if sequence_greater_than(sequence, remote) {
+            return false;
+        }
+        let distance = remote.wrapping_sub(sequence) as u32;
+        // Trop ancien pour le savoir : considéré comme déjà reçu, comme dans `record_received`
+        distance == 0 || distance > 32 || self.received_bits & (1 << (distance - 1)) != 0
+    }
+
+    /// Returns whether `sequence` was seen for the first time and whether it
+    /// is the most recent sequence received so far.
+    fn record_received(&mut self, sequence: u16) -> (bool, bool) {
+        let Some(remote) = self.remote_sequence else {
+            self.remote_sequence = Some(sequence);
+            return (true, true);
+        };
+
+        if sequence_greater_than(sequence, remote) {
+            let shift = sequence.wrapping_sub(remote) as u32;
+            self.received_bits = if shift > 32 {
+                0
+            } else {
+                // The previous `remote` becomes bit `shift - 1`
+                self.received_bits.checked_shl(shift).unwrap_or(0) | (1 << (shift - 1))
+            };
+            self.remote_sequence = Some(sequence);
+            return (true, true);
+        }
+
+        if sequence == remote {
+            return (false, false);
+        }
+        let distance = remote.wrapping_sub(sequence) as u32;
+        if distance > 32 {
+            // Too old to tell, treat it as already seen
+            return (false, false);
+        }
+        let bit = 1 << (distance - 1);
+        let is_new = self.received_bits & bit == 0;
+        self.received_bits |= bit;
+        (is_new, false)
+    }
+
+    fn process_acks(&mut self, ack: u16, ack_bits: u32) {
+        for distance in 0..=32u16 {
+            let sequence = ack.wrapping_sub(distance);
+            let age = self.local_sequence.wrapping_sub(sequence);
+            if (distance == 0 || ack_bits & (1 << (distance - 1)) != 0) && (1..=ACKED_HISTORY).contains(&age) {
+                self.set_acked(sequence, true);
+            }
+        }
+        let is_acked = |sequence: u16| {
+            if sequence == ack {
+                return true;
+            }
+            let distance = ack.wrapping_sub(sequence) as u32;
+            (1..=32).contains(&distance) && ack_bits & (1 << (distance - 1)) != 0
+        };
+        self.pending.retain(|pending| !pending.sequences.iter().any(|&sequence| is_acked(sequence)));
+    }
+}
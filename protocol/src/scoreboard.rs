@@ -0,0 +1,19 @@
+//! What `GameOver` tells about the match that just ended. The columns of
+//! the scoreboard depend on the game mode the server runs.
+
+use serde::{Serialize, Deserialize};
+use crate::player::{PlayerId, TeamId};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MatchWinner {
+    Player(PlayerId),
+    Team(TeamId),
+    Draw,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Scoreboard {
+    pub columns: Vec<String>,
+    pub rows: Vec<(PlayerId, Vec<i32>)>, // one value per column, best player first
+    pub team_scores: Vec<(TeamId, u32)>, // empty in modes without teams
+}
@@ -0,0 +1,135 @@
+//! Delta-compressed `GameState` snapshots.
+//!
+//! Positions and rotations are quantized to `u16`. The server sends each
+//! client only what changed since the last snapshot that client
+//! acknowledged (its baseline), as wrapping differences so small moves take
+//! a single byte once varint-encoded. A player missing from the baseline is
+//! sent as differences from zero, which is also how full snapshots are sent.
+
+use std::collections::{HashMap, VecDeque};
+use std::f32::consts::TAU;
+use serde::{Serialize, Deserialize};
+use crate::player::{PlayerId, PlayerSnapshot};
+
+/// Quantization steps per map unit: 1/64 of a cell, up to 1024 cells.
+pub const POSITION_SCALE: f32 = 64.0;
+
+/// Snapshots a client keeps as possible baselines. The server never picks a
+/// baseline older than this.
+pub const SNAPSHOT_HISTORY: usize = 32;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PackedPlayer {
+    pub x: u16,
+    pub y: u16,
+    pub rotation: u16, // a full turn is 65536
+    pub is_alive: bool,
+}
+
+pub type PackedWorld = HashMap<PlayerId, PackedPlayer>;
+
+impl PackedPlayer {
+    pub fn pack(snapshot: &PlayerSnapshot) -> Self {
+        Self {
+            x: (snapshot.x * POSITION_SCALE).round().clamp(0.0, u16::MAX as f32) as u16,
+            y: (snapshot.y * POSITION_SCALE).round().clamp(0.0, u16::MAX as f32) as u16,
+            rotation: (snapshot.rotation.rem_euclid(TAU) / TAU * 65536.0).round() as u32 as u16,
+            is_alive: snapshot.is_alive,
+        }
+    }
+
+    pub fn unpack(&self) -> PlayerSnapshot {
+        PlayerSnapshot {
+            x: self.x as f32 / POSITION_SCALE,
+            y: self.y as f32 / POSITION_SCALE,
+            rotation: self.rotation as f32 / 65536.0 * TAU,
+            is_alive: self.is_alive,
+        }
+    }
+}
+
+/// Fields of one player that differ from the baseline, `None` when unchanged.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct PlayerDelta {
+    pub id: PlayerId,
+    pub x: Option<i16>,
+    pub y: Option<i16>,
+    pub rotation: Option<i16>,
+    pub is_alive: Option<bool>,
+}
+
+/// Players that changed since `baseline`, and players that are gone.
+pub fn diff(baseline: Option<&PackedWorld>, current: &PackedWorld) -> (Vec<PlayerDelta>, Vec<PlayerId>) {
+    let mut changed = Vec::new();
+    for (&id, player) in current {
+        let previous = baseline.and_then(|world| world.get(&id));
+        let base = previous.copied().unwrap_or_default();
+        let field = |now: u16, before: u16| (now != before || previous.is_none()).then_some(now.wrapping_sub(before) as i16);
+        let delta = PlayerDelta {
+            id,
+            x: field(player.x, base.x),
+            y: field(player.y, base.y),
+            rotation: field(player.rotation, base.rotation),
+            is_alive: (previous.is_none() || player.is_alive != base.is_alive).then_some(player.is_alive),
+        };
+        if previous != Some(player) {
+            changed.push(delta);
+        }
+    }
+    let removed = baseline
+        .map(|world| world.keys().filter(|id| !current.contains_key(id)).copied().collect())
+        .unwrap_or_default();
+    (changed, removed)
+}
+
+/// Rebuilds the world from `baseline` and a delta. Returns `None` when a
+/// player absent from the baseline is not fully described.
+pub fn apply(baseline: Option<&PackedWorld>, players: &[PlayerDelta], removed: &[PlayerId]) -> Option<PackedWorld> {
+    let mut world = baseline.cloned().unwrap_or_default();
+    for id in removed {
+        world.remove(id);
+    }
+    for delta in players {
+        let previous = world.get(&delta.id).copied();
+        if previous.is_none() && (delta.x.is_none() || delta.y.is_none() || delta.rotation.is_none() || delta.is_alive.is_none()) {
+            return None;
+        }
+        let base = previous.unwrap_or_default();
+        let field = |change: Option<i16>, before: u16| change.map_or(before, |change| before.wrapping_add(change as u16));
+        world.insert(delta.id, PackedPlayer {
+            x: field(delta.x, base.x),
+            y: field(delta.y, base.y),
+            rotation: field(delta.rotation, base.rotation),
+            is_alive: delta.is_alive.unwrap_or(base.is_alive),
+        });
+    }
+    Some(world)
+}
+
+pub fn unpack_world(world: &PackedWorld) -> HashMap<PlayerId, PlayerSnapshot> {
+    world.iter().map(|(&id, player)| (id, player.unpack())).collect()
+}
+
+/// Client side: the last snapshots rebuilt, by tick, to decode the next
+/// deltas against.
+#[derive(Default)]
+pub struct SnapshotReceiver {
+    worlds: VecDeque<(u64, PackedWorld)>,
+}
+
+impl SnapshotReceiver {
+    /// Rebuilds the snapshot of `tick` and keeps it as a future baseline.
+    /// Returns `None` when its baseline is unknown.
+    pub fn receive(&mut self, tick: u64, baseline: Option<u64>, players: &[PlayerDelta], removed: &[PlayerId]) -> Option<&PackedWorld> {
+        let base = match baseline {
+            Some(baseline) => Some(&self.worlds.iter().find(|(tick, _)| *tick == baseline)?.1),
+            None => None,
+        };
+        let world = apply(base, players, removed)?;
+        if self.worlds.len() == SNAPSHOT_HISTORY * 2 {
+            self.worlds.pop_front();
+        }
+        self.worlds.push_back((tick, world));
+        self.worlds.back().map(|(_, world)| world)
+    }
+}
@@ -0,0 +1,222 @@
+//! Weapon definitions and the ammunition a player carries.
+//!
+//! Both sides run the same `Loadout`: the client to know when it may fire,
+//! the server to refuse shots the client should not have fired. Times are
+//! seconds on whatever clock the caller uses.
+
+use serde::{Serialize, Deserialize};
+
+/// Index into `WEAPONS`.
+pub type WeaponId = u8;
+
+#[derive(Debug, PartialEq)]
+pub struct WeaponDef {
+    pub name: &'static str,
+    pub fire_interval: f32, // seconds between two shots
+    pub damage: f32, // body hit within `falloff_start`
+    pub falloff_start: f32, // damage drops from here to `range`, see `damage`
+    pub spread: f32, // largest deviation of a shot from where the player aims, radians
+    pub range: f32,
+    pub magazine: u32,
+    pub reserve: u32, // rounds carried besides the magazine, at spawn
+    pub reload_time: f32, // seconds
+    pub model: &'static str, // viewmodel scene, relative to the client's assets
+    pub model_scale: f32,
+}
+
+pub const WEAPONS: [WeaponDef; 3] = [
+    WeaponDef {
+        name: "Rifle",
+        fire_interval: 0.15,
+        damage: 30.0,
+        falloff_start: 5.0,
+        spread: 0.02,
+        range: 12.0,
+        magazine: 30,
+        reserve: 90,
+        reload_time: 2.0,
+        model: "models/player/ak.glb#Scene0",
+        model_scale: 0.15,
+    },
+    WeaponDef {
+        name: "Pistol",
+        fire_interval: 0.4,
+        damage: 40.0,
+        falloff_start: 4.0,
+        spread: 0.01,
+        range: 10.0,
+        magazine: 12,
+        reserve: 36,
+        reload_time: 1.2,
+        model: "models/player/ak.glb#Scene0",
+        model_scale: 0.09,
+    },
+    WeaponDef {
+        name: "Shotgun",
+        fire_interval: 0.9,
+        damage: 90.0,
+        falloff_start: 1.5,
+        spread: 0.06,
+        range: 5.0,
+        magazine: 6,
+        reserve: 18,
+        reload_time: 2.5,
+        model: "models/player/ak.glb#Scene0",
+        model_scale: 0.2,
+    },
+];
+
+pub fn weapon(id: WeaponId) -> Option<&'static WeaponDef> {
+    WEAPONS.get(id as usize)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FireError {
+    UnknownWeapon,
+    TooSoon, // less than `fire_interval` after the previous shot
+    Reloading,
+    Empty, // magazine and reserve both empty
+}
+
+/// Ammunition of the weapon in hand, sent to its owner in every snapshot.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AmmoStatus {
+    pub weapon: WeaponId,
+    pub magazine: u16,
+    pub reserve: u16,
+    pub reload_ms: u16, // time left before the reload in progress ends, 0 when not reloading
+}
+
+/// The weapon in hand, and the magazine and reserve ammunition of every
+/// weapon. Reloading takes the weapon's `reload_time` and carries on when
+/// the weapon is put away. It starts on its own when a magazine runs empty.
+#[derive(Debug, Clone)]
+pub struct Loadout {
+    current: WeaponId,
+    magazines: [u32; WEAPONS.len()],
+    reserves: [u32; WEAPONS.len()],
+    reloading_until: [Option<f64>; WEAPONS.len()],
+    last_shot: Option<f64>,
+}
+
+impl Default for Loadout {
+    fn default() -> Self {
+        Self {
+            current: 0,
+            magazines: WEAPONS.map(|weapon| weapon.magazine),
+            reserves: WEAPONS.map(|weapon| weapon.reserve),
+            reloading_until: [None; WEAPONS.len()],
+            last_shot: None,
+        }
+    }
+}
+
+impl Loadout {
+    pub fn current(&self) -> WeaponId {
+        self.current
+    }
+
+    pub fn switch(&mut self, weapon_id: WeaponId) -> bool {
+        let known = weapon(weapon_id).is_some();
+        if known {
+            self.current = weapon_id;
+        }
+        known
+    }
+
+    /// Rounds left in the magazine of `weapon_id`, after finishing any
+    /// reload due by `now`.
+    pub fn magazine(&mut self, weapon_id: WeaponId, now: f64) -> u32 {
+        self.finish_reloads(now);
+        self.magazines.get(weapon_id as usize).copied().unwrap_or(0)
+    }
+
+    pub fn is_reloading(&self, weapon_id: WeaponId, now: f64) -> bool {
+        self.reload_left(weapon_id, now) > 0.0
+    }
+
+    /// How far along the reload of `weapon_id` is, from 0 to 1, while it
+    /// reloads.
+    pub fn reload_progress(&self, weapon_id: WeaponId, now: f64) -> Option<f32> {
+        let def = weapon(weapon_id)?;
+        let left = self.reload_left(weapon_id, now);
+        (left > 0.0).then(|| 1.0 - (left / def.reload_time as f64).clamp(0.0, 1.0) as f32)
+    }
+
+    /// Starts reloading the weapon in hand. Returns false when its magazine
+    /// is full, there is nothing to reload it with or it already reloads.
+    pub fn reload(&mut self, now: f64) -> bool {
+        self.finish_reloads(now);
+        let Some(def) = weapon(self.current) else {
+            return false;
+        };
+        let index = self.current as usize;
+        if self.is_reloading(self.current, now) || self.magazines[index] >= def.magazine || self.reserves[index] == 0 {
+            return false;
+        }
+        self.reloading_until[index] = Some(now + def.reload_time as f64);
+        true
+    }
+
+    /// Uses one round of the weapon in hand. `leeway` (seconds) is taken off
+    /// the fire interval and reload time, for shots that arrive bunched up
+    /// after crossing the network.
+    pub fn fire(&mut self, now: f64, leeway: f64) -> Result<&'static WeaponDef, FireError> {
+        let def = weapon(self.current).ok_or(FireError::UnknownWeapon)?;
+        self.finish_reloads(now + leeway);
+        let index = self.current as usize;
+        if self.is_reloading(self.current, now + leeway) {
+            return Err(FireError::Reloading);
+        }
+        if self.magazines[index] == 0 {
+            return Err(FireError::Empty);
+        }
+        if self.last_shot.is_some_and(|last| now + leeway - last < def.fire_interval as f64) {
+            return Err(FireError::TooSoon);
+        }
+        self.magazines[index] -= 1;
+        self.last_shot = Some(now);
+        if self.magazines[index] == 0 {
+            self.reload(now);
+        }
+        Ok(def)
+    }
+
+    pub fn status(&mut self, now: f64) -> AmmoStatus {
+        self.finish_reloads(now);
+        let index = self.current as usize;
+        AmmoStatus {
+            weapon: self.current,
+            magazine: self.magazines[index].min(u16::MAX as u32) as u16,
+            reserve: self.reserves[index].min(u16::MAX as u32) as u16,
+            reload_ms: (self.reload_left(self.current, now) * 1000.0).ceil().min(u16::MAX as f64) as u16,
+        }
+    }
+
+    /// Takes the ammunition of `status.weapon` from the server.
+    pub fn sync(&mut self, status: AmmoStatus, now: f64) {
+        let Some(def) = weapon(status.weapon) else {
+            return;
+        };
+        let index = status.weapon as usize;
+        self.magazines[index] = (status.magazine as u32).min(def.magazine);
+        self.reserves[index] = status.reserve as u32;
+        self.reloading_until[index] = (status.reload_ms > 0).then_some(now + status.reload_ms as f64 / 1000.0);
+    }
+
+    fn reload_left(&self, weapon_id: WeaponId, now: f64) -> f64 {
+        let until = self.reloading_until.get(weapon_id as usize).copied().flatten();
+        until.map_or(0.0, |until| (until - now).max(0.0))
+    }
+
+    fn finish_reloads(&mut self, now: f64) {
+        for (index, def) in WEAPONS.iter().enumerate() {
+            if self.reloading_until[index].is_some_and(|until| now >= until) {
+                self.reloading_until[index] = None;
+                let loaded = (def.magazine - self.magazines[index].min(def.magazine)).min(self.reserves[index]);
+                self.magazines[index] += loaded;
+                self.reserves[index] -= loaded;
+            }
+        }
+    }
+}
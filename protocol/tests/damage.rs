@@ -0,0 +1,40 @@
+use maze_wars_protocol::damage::*;
+use maze_wars_protocol::weapon::WEAPONS;
+
+#[test]
+fn pitch_decides_the_hit_zone() {
+    assert_eq!(hit_zone(5.0, -0.1), Some(HitZone::Body));
+    // Au-dessus de la tête, ou dans le sol avant d'atteindre la cible
+    assert_eq!(hit_zone(5.0, 0.1), None);
+    assert_eq!(hit_zone(5.0, -0.5), None);
+    assert_eq!(hit_zone(5.0, f32::NAN), None);
+}
+
+#[test]
+fn zone_is_where_the_shot_reaches_the_target() {
+    for distance in [0.5, 5.0, 12.0] {
+        // Tir horizontal depuis les yeux : à hauteur de tête
+        assert_eq!(hit_zone(distance, 0.0), Some(HitZone::Head));
+        // Visé vers la poitrine, à 1.2
+        let pitch = ((1.2 - EYE_HEIGHT) / distance).atan();
+        assert_eq!(hit_zone(distance, pitch), Some(HitZone::Body));
+    }
+}
+
+#[test]
+fn damage_falls_off_with_distance() {
+    let rifle = &WEAPONS[0];
+    let close = shot_damage(rifle, 1.0, HitZone::Body);
+    assert_eq!(close, shot_damage(rifle, rifle.falloff_start, HitZone::Body));
+    assert!(shot_damage(rifle, rifle.falloff_start + 1.0, HitZone::Body) < close);
+    assert_eq!(shot_damage(rifle, rifle.range, HitZone::Body), (close as f32 * MIN_FALLOFF).round() as u32);
+    assert_eq!(shot_damage(rifle, 1.0, HitZone::Head), (close as f32 * HEADSHOT_MULTIPLIER) as u32);
+}
+
+#[test]
+fn armor_absorbs_part_of_the_damage_until_depleted() {
+    assert_eq!(apply_damage(100, 50, 40), (80, 30));
+    assert_eq!(apply_damage(80, 10, 40), (50, 0));
+    assert_eq!(apply_damage(50, 0, 40), (10, 0));
+    assert_eq!(apply_damage(10, 0, 40), (0, 0));
+}
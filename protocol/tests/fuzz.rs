@@ -0,0 +1,61 @@
+use std::time::Instant;
+use proptest::prelude::*;
+use maze_wars_protocol::codec::{self, SequenceHeader, MAGIC, PROTOCOL_VERSION};
+use maze_wars_protocol::reliability::Endpoint;
+use maze_wars_protocol::{ClientMessage, ServerMessage, MAX_PACKET_SIZE};
+
+/// A packet with a valid prefix, so the fuzzed bytes reach the sequence
+/// header and body decoding instead of being rejected on the magic.
+fn with_valid_prefix(kind: u8, rest: &[u8]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend_from_slice(&PROTOCOL_VERSION.to_le_bytes());
+    bytes.push(kind);
+    bytes.extend_from_slice(rest);
+    bytes
+}
+
+proptest! {
+    #[test]
+    fn decode_never_panics(bytes in prop::collection::vec(any::<u8>(), 0..MAX_PACKET_SIZE)) {
+        let _ = codec::read_header(&bytes);
+        let _ = codec::decode::<ClientMessage>(&bytes);
+        let _ = codec::decode::<ServerMessage>(&bytes);
+    }
+
+    #[test]
+    fn decode_never_panics_past_the_header(kind in 0u8..13, rest in prop::collection::vec(any::<u8>(), 0..512)) {
+        let bytes = with_valid_prefix(kind, &rest);
+        let _ = codec::decode::<ClientMessage>(&bytes);
+        let _ = codec::decode::<ServerMessage>(&bytes);
+    }
+
+    #[test]
+    fn decode_never_panics_on_random_bodies(
+        sequence in any::<u16>(),
+        reliable in any::<bool>(),
+        body in prop::collection::vec(any::<u8>(), 0..512),
+    ) {
+        // Valid header and sequence header, garbage message body
+        let header = SequenceHeader { sequence, reliable_id: reliable.then_some(sequence), ..Default::default() };
+        let valid = codec::encode(&header, &ClientMessage::Heartbeat).unwrap();
+        let mut bytes = valid[..valid.len() - 1].to_vec();
+        bytes.extend_from_slice(&body);
+        for kind in 0..13 {
+            bytes[6] = kind;
+            let _ = codec::decode::<ClientMessage>(&bytes);
+            let _ = codec::decode::<ServerMessage>(&bytes);
+        }
+    }
+
+    #[test]
+    fn endpoint_survives_garbage(packets in prop::collection::vec(prop::collection::vec(any::<u8>(), 0..256), 1..32)) {
+        let mut endpoint = Endpoint::new();
+        for packet in &packets {
+            let _ = endpoint.receive::<ClientMessage>(&with_valid_prefix(packet.first().copied().unwrap_or(0) % 10, packet), Instant::now());
+        }
+        // Still usable afterwards
+        let mut client = Endpoint::new();
+        let join = client.send(&ClientMessage::Join { name: "alice".to_string() }, Instant::now()).unwrap();
+        prop_assert!(endpoint.receive::<ClientMessage>(&join, Instant::now()).is_ok());
+    }
+}
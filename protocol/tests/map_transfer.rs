@@ -0,0 +1,63 @@
+use maze_wars_protocol::codec::{self, SequenceHeader};
+use maze_wars_protocol::map_transfer::{self, MapAssembler, MAP_CHUNK_SIZE};
+use maze_wars_protocol::{Map, ServerMessage, MAX_PACKET_SIZE};
+
+/// A map far too big for one datagram, with a pattern that does not
+/// compress to almost nothing.
+fn large_map(width: usize, height: usize) -> Map {
+    let cells = (0..height)
+        .map(|y| (0..width).map(|x| (x * 7 + y * 13) % 5 == 0 || x == 0 || y == 0).collect())
+        .collect();
+    Map::from_cells(cells, 42)
+}
+
+#[test]
+fn cells_survive_compression() {
+    let map = large_map(31, 17);
+    let data = map_transfer::compress(&map.cells);
+    assert_eq!(map_transfer::decompress(&data, 31, 17), Some(map.cells));
+
+    // Starting with a wall means a first run of zero floor cells
+    let cells = vec![vec![true, true, false], vec![false, true, true]];
+    let data = map_transfer::compress(&cells);
+    assert_eq!(data, vec![0, 2, 2, 2]);
+    assert_eq!(map_transfer::decompress(&data, 3, 2), Some(cells));
+}
+
+#[test]
+fn inconsistent_data_is_refused() {
+    let data = map_transfer::compress(&[vec![false, true, false]]);
+    assert_eq!(map_transfer::decompress(&data, 2, 2), None);
+    assert_eq!(map_transfer::decompress(&data, 4, 1), None);
+    assert_eq!(map_transfer::decompress(&[0xFF; 11], 3, 1), None);
+    assert_eq!(map_transfer::decompress(&[1], 1 << 16, 1 << 16), None);
+}
+
+#[test]
+fn large_map_is_reassembled_from_chunks_in_any_order() {
+    let map = large_map(400, 300);
+    let (info, chunks) = map_transfer::split(&map, 7);
+    assert!(chunks.len() > 1);
+
+    let mut packets = Vec::new();
+    for (index, data) in chunks.iter().enumerate() {
+        assert!(data.len() <= MAP_CHUNK_SIZE);
+        let message = ServerMessage::MapChunk { info, index: index as u16, data: data.clone() };
+        let packet = codec::encode(&SequenceHeader::default(), &message).unwrap();
+        assert!(packet.len() <= MAX_PACKET_SIZE);
+        packets.push(packet);
+    }
+
+    let mut assembler = MapAssembler::new(info);
+    let mut assembled = None;
+    for packet in packets.iter().rev() {
+        let Some(ServerMessage::MapChunk { info, index, data }) = codec::decode(packet).unwrap().message else {
+            panic!("expected a map chunk");
+        };
+        assert!(assembled.is_none(), "map completed before its last chunk");
+        // Chunks of another map are ignored
+        assert_eq!(assembler.add(map_transfer::MapInfo { id: 8, ..info }, index, data.clone()), None);
+        assembled = assembler.add(info, index, data);
+    }
+    assert_eq!(assembled, Some(map));
+}
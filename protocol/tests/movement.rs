@@ -0,0 +1,94 @@
+use maze_wars_protocol::map::{is_valid_move, PLAYER_SIZE};
+use maze_wars_protocol::movement::{apply_move, players_overlap};
+use maze_wars_protocol::Map;
+
+/// `#` is a wall, cell (x, y) is character x of line y.
+fn map(rows: &[&str]) -> Map {
+    let cells = rows.iter().map(|row| row.chars().map(|c| c == '#').collect()).collect();
+    Map::from_cells(cells, 0)
+}
+
+fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+    (a.0 - b.0).abs() < 0.01 && (a.1 - b.1).abs() < 0.01
+}
+
+#[test]
+fn players_fit_in_one_cell_corridors() {
+    let map = map(&[
+        "#####",
+        "#...#",
+        "#####",
+    ]);
+    assert!(is_valid_move(&map, 2.0, 1.0));
+    assert!(is_valid_move(&map, 0.75, 1.0));
+    assert!(!is_valid_move(&map, 0.7, 1.0));
+    assert!(!is_valid_move(&map, 2.0, 1.3));
+    assert!(!is_valid_move(&map, -3.0, 1.0));
+}
+
+#[test]
+fn diagonal_moves_slide_along_walls() {
+    let map = map(&[
+        "#######",
+        "#.....#",
+        "#.....#",
+        "#######",
+    ]);
+    let diagonal = (std::f32::consts::FRAC_1_SQRT_2, -std::f32::consts::FRAC_1_SQRT_2);
+    let mut position = (2.0, 1.5);
+    for _ in 0..5 {
+        position = apply_move(&map, position, diagonal, &[]);
+    }
+    // Plaqué contre le mur du haut mais toujours en train d'avancer
+    assert!(close(position, (2.0 + 5.0 * 0.3 * diagonal.0, 0.75)), "{:?}", position);
+    assert!(is_valid_move(&map, position.0, position.1));
+}
+
+#[test]
+fn blocked_moves_stop_flush_against_the_wall() {
+    let map = map(&[
+        "#####",
+        "#...#",
+        "#####",
+    ]);
+    let position = apply_move(&map, (3.1, 1.0), (1.0, 0.0), &[]);
+    assert!(close(position, (3.25, 1.0)), "{:?}", position);
+    assert_eq!(apply_move(&map, position, (1.0, 0.0), &[]), position);
+}
+
+#[test]
+fn corners_do_not_let_players_through() {
+    // Un pilier en diagonale : ni x ni y seul ne le touche, seulement les deux ensemble
+    let map = map(&[
+        "#####",
+        "#...#",
+        "#.#.#",
+        "#...#",
+        "#####",
+    ]);
+    let position = apply_move(&map, (1.2, 1.2), (0.6, 0.8), &[]);
+    assert!(is_valid_move(&map, position.0, position.1));
+    // x passe, y est arrêté au bord de la case du pilier
+    assert!(close(position, (1.38, 1.25)), "{:?}", position);
+
+    // Coin intérieur : bloqué sur les deux axes
+    let corner = (0.75, 3.25);
+    assert_eq!(apply_move(&map, corner, (-0.6, 0.8), &[]), corner);
+}
+
+#[test]
+fn players_block_each_other() {
+    let map = map(&[
+        "#######",
+        "#.....#",
+        "#######",
+    ]);
+    let other = (3.0, 1.0);
+    let position = apply_move(&map, (2.3, 1.0), (1.0, 0.0), &[other]);
+    assert!(close(position, (3.0 - PLAYER_SIZE, 1.0)), "{:?}", position);
+    assert!(!players_overlap(position, other));
+
+    // Deux joueurs apparus au même endroit peuvent se séparer
+    let stacked = apply_move(&map, other, (-1.0, 0.0), &[other]);
+    assert!(close(stacked, (2.7, 1.0)), "{:?}", stacked);
+}
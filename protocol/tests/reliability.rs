@@ -0,0 +1,94 @@
+use std::time::Instant;
+use maze_wars_protocol::reliability::{Endpoint, ACKED_HISTORY, RESEND_AFTER};
+use maze_wars_protocol::{ClientMessage, ServerMessage};
+
+fn death() -> ServerMessage {
+    ServerMessage::PlayerDied { player: 2 }
+}
+
+#[test]
+fn reliable_messages_are_resent_until_acked() {
+    let mut server = Endpoint::new();
+    let mut client = Endpoint::new();
+    let start = Instant::now();
+
+    // The first transmission is lost
+    let _lost = server.send(&death(), start).unwrap();
+    assert!(server.resend_due(start).is_empty());
+
+    let resent = server.resend_due(start + RESEND_AFTER);
+    assert_eq!(resent.len(), 1);
+    let received = client.receive::<ServerMessage>(&resent[0], start).unwrap().unwrap();
+    assert_eq!(received.message, death());
+
+    // Once the ack comes back nothing is pending anymore
+    let ack = client.ack(start + RESEND_AFTER);
+    assert!(server.receive::<ClientMessage>(&ack, start).unwrap().is_none());
+    assert!(server.resend_due(start + RESEND_AFTER * 3).is_empty());
+}
+
+#[test]
+fn duplicated_reliable_messages_are_delivered_once() {
+    let mut server = Endpoint::new();
+    let mut client = Endpoint::new();
+    let start = Instant::now();
+
+    let first = server.send(&death(), start).unwrap();
+    let copy = server.resend_due(start + RESEND_AFTER).pop().unwrap();
+    assert!(client.receive::<ServerMessage>(&first, start).unwrap().is_some());
+    assert!(client.receive::<ServerMessage>(&copy, start).unwrap().is_none());
+    assert!(client.receive::<ServerMessage>(&first, start).unwrap().is_none());
+}
+
+#[test]
+fn out_of_order_snapshots_are_not_latest() {
+    let mut server = Endpoint::new();
+    let mut client = Endpoint::new();
+    let now = Instant::now();
+    let snapshot = ServerMessage::GameState { tick: 1, baseline: None, players: Vec::new(), removed: Vec::new(), last_input: 0, ammo: Default::default(), clock: Default::default() };
+
+    let older = server.send(&snapshot, now).unwrap();
+    let newer = server.send(&snapshot, now).unwrap();
+    assert!(client.receive::<ServerMessage>(&newer, now).unwrap().unwrap().is_latest);
+    assert!(!client.receive::<ServerMessage>(&older, now).unwrap().unwrap().is_latest);
+    // Unreliable duplicates are dropped
+    assert!(client.receive::<ServerMessage>(&newer, now).unwrap().is_none());
+}
+
+#[test]
+fn acked_sequences_are_remembered() {
+    let mut server = Endpoint::new();
+    let mut client = Endpoint::new();
+    let now = Instant::now();
+    let snapshot = ServerMessage::GameState { tick: 1, baseline: None, players: Vec::new(), removed: Vec::new(), last_input: 0, ammo: Default::default(), clock: Default::default() };
+
+    let _lost = server.send(&snapshot, now).unwrap();
+    let delivered = server.send(&snapshot, now).unwrap();
+    assert!(!server.was_acked(0) && !server.was_acked(1));
+    client.receive::<ServerMessage>(&delivered, now).unwrap();
+    server.receive::<ClientMessage>(&client.ack(now), now).unwrap();
+    assert!(server.was_acked(1));
+    assert!(!server.was_acked(0));
+
+    // Acks of sequences from too long ago are forgotten
+    for _ in 0..ACKED_HISTORY {
+        server.send(&snapshot, now).unwrap();
+    }
+    assert!(!server.was_acked(1));
+}
+
+#[test]
+fn refused_messages_are_not_acked() {
+    let mut server = Endpoint::new();
+    let mut client = Endpoint::new();
+    let now = Instant::now();
+    let snapshot = ServerMessage::GameState { tick: 1, baseline: None, players: Vec::new(), removed: Vec::new(), last_input: 0, ammo: Default::default(), clock: Default::default() };
+
+    let refused = server.send(&snapshot, now).unwrap();
+    let accepted = server.send(&snapshot, now).unwrap();
+    assert!(client.receive_if::<ServerMessage>(&refused, now, |_| false).unwrap().is_none());
+    assert!(client.receive_if::<ServerMessage>(&accepted, now, |_| true).unwrap().is_some());
+    server.receive::<ClientMessage>(&client.ack(now), now).unwrap();
+    assert!(!server.was_acked(0));
+    assert!(server.was_acked(1));
+}
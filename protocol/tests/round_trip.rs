@@ -0,0 +1,171 @@
+use std::collections::HashMap;
+use maze_wars_protocol::map::{Map, MAP_HEIGHT, MAP_WIDTH};
+use maze_wars_protocol::damage::HitZone;
+use maze_wars_protocol::map_transfer;
+use maze_wars_protocol::phase::{MatchClock, MatchPhase};
+use maze_wars_protocol::scoreboard::{MatchWinner, Scoreboard};
+use maze_wars_protocol::weapon::AmmoStatus;
+use maze_wars_protocol::snapshot::{self, PackedPlayer};
+use maze_wars_protocol::codec::{self, CodecError, Packet, SequenceHeader};
+use maze_wars_protocol::{ClientMessage, PlayerSnapshot, ServerMessage};
+
+fn sample_map() -> Map {
+    let mut cells = vec![vec![false; MAP_WIDTH]; MAP_HEIGHT];
+    for (y, row) in cells.iter_mut().enumerate() {
+        for (x, cell) in row.iter_mut().enumerate() {
+            *cell = x == 0 || y == 0 || x == MAP_WIDTH - 1 || y == MAP_HEIGHT - 1;
+        }
+    }
+    cells[5][3..9].iter_mut().for_each(|cell| *cell = true);
+    Map::from_cells(cells, 1)
+}
+
+fn round_trip_client(message: ClientMessage) {
+    let bytes = codec::encode(&SequenceHeader::default(), &message).unwrap();
+    assert_eq!(codec::decode::<ClientMessage>(&bytes).unwrap().message, Some(message));
+}
+
+fn round_trip_server(message: ServerMessage) {
+    let bytes = codec::encode(&SequenceHeader::default(), &message).unwrap();
+    assert_eq!(codec::decode::<ServerMessage>(&bytes).unwrap().message, Some(message));
+}
+
+#[test]
+fn client_messages_round_trip() {
+    round_trip_client(ClientMessage::Join { name: "alice".to_string() });
+    round_trip_client(ClientMessage::Move { sequence: 42, direction: (0.6, -0.8) });
+    round_trip_client(ClientMessage::Shoot { weapon: 2, direction: (-1.0, 0.0), pitch: 0.1, view_tick: 1200 });
+    round_trip_client(ClientMessage::Heartbeat);
+    round_trip_client(ClientMessage::Leave);
+    round_trip_client(ClientMessage::Reconnect { session: u64::MAX, name: "alice".to_string() });
+    round_trip_client(ClientMessage::MapReceived { map_id: 3 });
+    round_trip_client(ClientMessage::Reload { weapon: 2 });
+}
+
+#[test]
+fn server_messages_round_trip() {
+    round_trip_server(ServerMessage::Welcome {
+        player_id: 1,
+        name: "alice".to_string(),
+        session: 0x0123_4567_89AB_CDEF,
+        difficulty: 2,
+    });
+
+    let mut world = HashMap::new();
+    world.insert(1, PackedPlayer::pack(&PlayerSnapshot { x: 1.5, y: 2.5, rotation: 0.25, is_alive: true }));
+    world.insert(2, PackedPlayer::pack(&PlayerSnapshot { x: 10.5, y: 3.5, rotation: -1.0, is_alive: false }));
+    let (players, _) = snapshot::diff(None, &world);
+    let ammo = AmmoStatus { weapon: 1, magazine: 7, reserve: 24, reload_ms: 650 };
+    let clock = MatchClock { phase: MatchPhase::InProgress, remaining_ms: 184_250 };
+    round_trip_server(ServerMessage::GameState { tick: 1203, baseline: Some(1201), players, removed: vec![3], last_input: 42, ammo, clock });
+
+    round_trip_server(ServerMessage::PlayerJoined { player: 2, name: "bob".to_string(), team: None });
+    round_trip_server(ServerMessage::PlayerJoined { player: 3, name: "carol".to_string(), team: Some(1) });
+    round_trip_server(ServerMessage::PlayerTeam { player: 3, team: Some(0) });
+    let (info, chunks) = map_transfer::split(&sample_map(), 3);
+    round_trip_server(ServerMessage::MapChunk { info, index: 0, data: chunks[0].clone() });
+    round_trip_server(ServerMessage::PlayerShot { shooter: 1, target: 2, zone: HitZone::Head, damage: 80, health: 60, armor: 10, from: (3.5, 2.0) });
+    round_trip_server(ServerMessage::PlayerDied { player: 2 });
+    round_trip_server(ServerMessage::ShotFired { shooter: 1, impact: (4.5, 5.0), hit_wall: true });
+    round_trip_server(ServerMessage::PlayerLeft { player: 2 });
+    round_trip_server(ServerMessage::PlayerRespawned { player: 2, position: (4.5, 7.5) });
+    round_trip_server(ServerMessage::PhaseChanged { phase: MatchPhase::Countdown });
+    let scoreboard = Scoreboard {
+        columns: vec!["Kills".to_string(), "Deaths".to_string()],
+        rows: vec![(1, vec![3, 1]), (2, vec![1, 3])],
+        team_scores: vec![(0, 4), (1, 2)],
+    };
+    round_trip_server(ServerMessage::GameOver { winner: MatchWinner::Player(1), scoreboard });
+    round_trip_server(ServerMessage::GameOver { winner: MatchWinner::Team(1), scoreboard: Scoreboard::default() });
+}
+
+#[test]
+fn map_dimensions_follow_cells() {
+    let map = sample_map();
+    assert_eq!(map.map_width, MAP_WIDTH);
+    assert_eq!(map.map_height, MAP_HEIGHT);
+    assert!(map.is_wall(0, 0));
+    assert!(map.is_wall(4, 5));
+    assert!(!map.is_wall(4, 6));
+}
+
+#[test]
+fn raycast_stops_at_first_wall() {
+    let map = sample_map();
+    // Wall cells (3..9, 5) span y in [4.5, 5.5]
+    let hit = map.raycast((4.0, 2.0), (0.0, 1.0), 10.0).unwrap();
+    assert!((hit.distance - 2.5).abs() < 1e-5);
+    assert!((hit.point.1 - 4.5).abs() < 1e-5);
+
+    // Out of range, and parallel to the wall
+    assert!(map.raycast((4.0, 2.0), (0.0, 1.0), 2.0).is_none());
+    let side = map.raycast((1.0, 3.0), (1.0, 0.0), 30.0).unwrap();
+    assert!((side.point.0 - (MAP_WIDTH as f32 - 1.5)).abs() < 1e-5);
+}
+
+#[test]
+fn header_carries_magic_version_and_kind() {
+    let bytes = codec::encode(&SequenceHeader::default(), &ClientMessage::Shoot { weapon: 0, direction: (1.0, 0.0), pitch: 0.0, view_tick: 0 }).unwrap();
+    let header = codec::read_header(&bytes).unwrap();
+    assert_eq!(&bytes[..4], &codec::MAGIC);
+    assert_eq!(header.version, codec::PROTOCOL_VERSION);
+    assert_eq!(header.kind, ClientMessage::KIND_SHOOT);
+}
+
+#[test]
+fn sequence_header_round_trips() {
+    let header = SequenceHeader { sequence: 65535, ack: Some(12), ack_bits: 0xF0F0_0001, reliable_id: Some(7), session: None };
+    let message = ClientMessage::Join { name: "alice".to_string() };
+    let bytes = codec::encode(&header, &message).unwrap();
+    assert_eq!(codec::decode::<ClientMessage>(&bytes).unwrap(), Packet { header, message: Some(message) });
+
+    let ack_only = SequenceHeader { sequence: 3, ack: None, ack_bits: 0, reliable_id: None, session: None };
+    let bytes = codec::encode_ack(&ack_only);
+    assert_eq!(codec::decode::<ServerMessage>(&bytes).unwrap(), Packet { header: ack_only, message: None });
+}
+
+#[test]
+fn session_token_round_trips() {
+    let header = SequenceHeader { sequence: 9, reliable_id: Some(2), session: Some(0xDEAD_BEEF_0BAD_F00D), ..Default::default() };
+    let message = ClientMessage::Shoot { weapon: 0, direction: (1.0, 0.0), pitch: 0.0, view_tick: 5 };
+    let bytes = codec::encode(&header, &message).unwrap();
+    assert_eq!(codec::read_headers(&bytes).unwrap().1.session, Some(0xDEAD_BEEF_0BAD_F00D));
+    assert_eq!(codec::decode::<ClientMessage>(&bytes).unwrap(), Packet { header, message: Some(message) });
+
+    let ack_only = SequenceHeader { session: Some(42), ..Default::default() };
+    let bytes = codec::encode_ack(&ack_only);
+    assert_eq!(codec::read_headers(&bytes).unwrap().1.session, Some(42));
+    assert_eq!(codec::decode::<ServerMessage>(&bytes).unwrap().header, ack_only);
+
+    let bytes = codec::encode(&SequenceHeader::default(), &ClientMessage::Heartbeat).unwrap();
+    assert_eq!(codec::read_headers(&bytes).unwrap().1.session, None);
+}
+
+#[test]
+fn mismatched_version_is_reported() {
+    let mut bytes = codec::encode(&SequenceHeader::default(), &ClientMessage::Join { name: "alice".to_string() }).unwrap();
+    bytes[4..6].copy_from_slice(&(codec::PROTOCOL_VERSION + 1).to_le_bytes());
+    match codec::decode::<ClientMessage>(&bytes) {
+        Err(CodecError::VersionMismatch { ours, theirs }) => {
+            assert_eq!(ours, codec::PROTOCOL_VERSION);
+            assert_eq!(theirs, codec::PROTOCOL_VERSION + 1);
+        }
+        other => panic!("expected a version mismatch, got {:?}", other),
+    }
+}
+
+#[test]
+fn rejection_is_readable_by_any_version() {
+    let mut bytes = codec::encode_rejection("please update");
+    bytes[4..6].copy_from_slice(&0u16.to_le_bytes());
+    match codec::decode::<ServerMessage>(&bytes) {
+        Err(CodecError::Rejected(reason)) => assert_eq!(reason, "please update"),
+        other => panic!("expected a rejection, got {:?}", other),
+    }
+}
+
+#[test]
+fn foreign_packets_are_refused() {
+    assert!(matches!(codec::decode::<ClientMessage>(b"{\"Join\":{}}"), Err(CodecError::BadMagic)));
+    assert!(matches!(codec::decode::<ClientMessage>(b"MZ"), Err(CodecError::Truncated)));
+}
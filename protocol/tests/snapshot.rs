@@ -0,0 +1,59 @@
+use maze_wars_protocol::snapshot::{self, PackedPlayer, PackedWorld, SnapshotReceiver, POSITION_SCALE};
+use maze_wars_protocol::PlayerSnapshot;
+
+fn player(x: f32, y: f32, rotation: f32) -> PackedPlayer {
+    PackedPlayer::pack(&PlayerSnapshot { x, y, rotation, is_alive: true })
+}
+
+fn world(players: &[(u32, PackedPlayer)]) -> PackedWorld {
+    players.iter().copied().collect()
+}
+
+#[test]
+fn quantization_keeps_positions_and_angles_close() {
+    let original = PlayerSnapshot { x: 12.34, y: 0.3, rotation: -2.5, is_alive: false };
+    let unpacked = PackedPlayer::pack(&original).unpack();
+    assert!((unpacked.x - original.x).abs() <= 0.5 / POSITION_SCALE);
+    assert!((unpacked.y - original.y).abs() <= 0.5 / POSITION_SCALE);
+    assert!((unpacked.rotation.sin() - original.rotation.sin()).abs() < 1e-3);
+    assert!((unpacked.rotation.cos() - original.rotation.cos()).abs() < 1e-3);
+    assert!(!unpacked.is_alive);
+}
+
+#[test]
+fn deltas_only_carry_changes() {
+    let baseline = world(&[(1, player(3.5, 4.5, 0.0)), (2, player(8.5, 8.5, 1.0)), (3, player(1.5, 1.5, 0.0))]);
+    let mut moved = player(3.8, 4.5, 0.0);
+    moved.is_alive = false;
+    let current = world(&[(1, moved), (2, player(8.5, 8.5, 1.0)), (4, player(20.5, 2.5, 3.0))]);
+
+    let (players, removed) = snapshot::diff(Some(&baseline), &current);
+    assert_eq!(removed, vec![3]);
+    assert_eq!(players.len(), 2, "player 2 did not change");
+    let delta = players.iter().find(|delta| delta.id == 1).unwrap();
+    assert_eq!((delta.x, delta.y, delta.rotation, delta.is_alive), (Some(19), None, None, Some(false)));
+
+    assert_eq!(snapshot::apply(Some(&baseline), &players, &removed), Some(current.clone()));
+    // Without the baseline, player 1 is not fully described
+    assert_eq!(snapshot::apply(None, &players, &removed), None);
+
+    // A full snapshot needs no baseline
+    let (players, removed) = snapshot::diff(None, &current);
+    assert!(removed.is_empty());
+    assert_eq!(snapshot::apply(None, &players, &removed), Some(current));
+}
+
+#[test]
+fn receiver_decodes_against_the_baseline_tick() {
+    let first = world(&[(1, player(3.5, 4.5, 0.0))]);
+    let second = world(&[(1, player(3.5, 4.8, 0.5)), (2, player(9.5, 9.5, 0.0))]);
+    let mut receiver = SnapshotReceiver::default();
+
+    let (players, removed) = snapshot::diff(None, &first);
+    assert_eq!(receiver.receive(10, None, &players, &removed), Some(&first));
+
+    let (players, removed) = snapshot::diff(Some(&first), &second);
+    assert_eq!(receiver.receive(11, Some(9), &players, &removed), None, "tick 9 was never received");
+    assert_eq!(receiver.receive(12, Some(10), &players, &removed), Some(&second));
+    assert_eq!(snapshot::unpack_world(&second)[&2], player(9.5, 9.5, 0.0).unpack());
+}
@@ -0,0 +1,66 @@
+use maze_wars_protocol::weapon::{weapon, FireError, Loadout, WEAPONS};
+
+#[test]
+fn fire_rate_is_enforced() {
+    let mut loadout = Loadout::default();
+    let interval = WEAPONS[0].fire_interval as f64;
+    assert!(loadout.fire(0.0, 0.0).is_ok());
+    assert_eq!(loadout.fire(interval / 2.0, 0.0), Err(FireError::TooSoon));
+    // La marge accepte un tir arrivé un peu trop tôt
+    assert!(loadout.fire(interval - 0.05, 0.1).is_ok());
+}
+
+#[test]
+fn empty_magazines_reload_on_their_own() {
+    let mut loadout = Loadout::default();
+    assert!(loadout.switch(1));
+    let pistol = weapon(1).unwrap();
+    let mut now = 0.0;
+    for _ in 0..pistol.magazine {
+        assert!(loadout.fire(now, 0.0).is_ok());
+        now += pistol.fire_interval as f64;
+    }
+    assert_eq!(loadout.fire(now, 0.0), Err(FireError::Reloading));
+    assert!(loadout.is_reloading(1, now));
+
+    // Les autres armes ne sont pas concernées, et le rechargement continue rangé
+    assert!(loadout.switch(0));
+    assert!(loadout.fire(now, 0.0).is_ok());
+    assert_eq!(loadout.magazine(1, now + pistol.reload_time as f64), pistol.magazine);
+}
+
+#[test]
+fn unknown_weapons_cannot_be_selected() {
+    let mut loadout = Loadout::default();
+    assert!(!loadout.switch(WEAPONS.len() as u8));
+    assert_eq!(loadout.current(), 0);
+}
+
+#[test]
+fn reloading_draws_from_the_reserve() {
+    let mut loadout = Loadout::default();
+    let shotgun = weapon(2).unwrap();
+    assert!(loadout.switch(2));
+    // Chargeur plein : rien à recharger
+    assert!(!loadout.reload(0.0));
+
+    let mut now = 0.0;
+    for _ in 0..2 {
+        assert!(loadout.fire(now, 0.0).is_ok());
+        now += shotgun.fire_interval as f64;
+    }
+    assert!(loadout.reload(now));
+    assert_eq!(loadout.fire(now, 0.0), Err(FireError::Reloading));
+    now += shotgun.reload_time as f64;
+    let status = loadout.status(now);
+    assert_eq!((status.magazine, status.reserve, status.reload_ms), (6, 16, 0));
+
+    // Une fois la réserve épuisée, l'arme est vide
+    for _ in 0..(status.magazine + status.reserve) {
+        while loadout.fire(now, 0.0).is_err() {
+            now += 0.1;
+        }
+    }
+    assert_eq!(loadout.fire(now + 10.0, 0.0), Err(FireError::Empty));
+    assert!(!loadout.reload(now + 10.0));
+}
@@ -0,0 +1,132 @@
+use std::env;
+use std::str::FromStr;
+use std::time::Duration;
+use crate::game_mode::GameModeKind;
+
+#[derive(Clone, Debug)]
+pub struct ServerConfig {
+    pub tick_rate: u32, // simulation steps (and snapshots) per second
+    pub max_rewind: Duration, // how far back a shot may be checked against past positions
+    pub idle_timeout: Duration, // players not heard from for this long are removed
+    pub reconnect_grace: Duration, // how long a timed out player can still reconnect with their session
+    pub view_radius: f32, // players further away than this (in cells) are never sent, even in line of sight
+    pub hearing_radius: f32, // a player who just shot is sent to everyone this close, walls or not
+    pub audible_for: Duration, // how long a shot keeps its shooter audible
+    pub move_burst: Duration, // movement time a player can save up, absorbs network jitter
+    pub kick_after_violations: u32, // rejected inputs in a row before a player is kicked, 0 never kicks
+    pub mode: GameModeKind,
+    pub friendly_fire: bool, // whether shots hurt teammates, in modes with teams
+    pub min_players: usize, // players needed to start a match
+    pub countdown: Duration, // between enough players joining and the match starting
+    pub match_duration: Duration,
+    pub post_match: Duration, // how long scores are shown before the next match
+    pub respawn_delay: Duration, // how long a killed player stays dead
+    pub spawn_protection: Duration, // how long a respawned player cannot be hit, unless they shoot
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            tick_rate: 30,
+            max_rewind: Duration::from_millis(250),
+            idle_timeout: Duration::from_secs(10),
+            reconnect_grace: Duration::from_secs(60),
+            view_radius: 12.0,
+            hearing_radius: 15.0,
+            audible_for: Duration::from_millis(1500),
+            move_burst: Duration::from_millis(400),
+            kick_after_violations: 30,
+            mode: GameModeKind::FreeForAll,
+            friendly_fire: false,
+            min_players: 2,
+            countdown: Duration::from_secs(5),
+            match_duration: Duration::from_secs(300),
+            post_match: Duration::from_secs(10),
+            respawn_delay: Duration::from_secs(3),
+            spawn_protection: Duration::from_millis(2000),
+        }
+    }
+}
+
+impl ServerConfig {
+    /// Builds the config from `--flag value` pairs on the command line,
+    /// keeping the default for anything missing or unparsable.
+    pub fn from_args() -> Self {
+        let mut config = Self::default();
+        let mut args = env::args().skip(1);
+        while let Some(flag) = args.next() {
+            let Some(value) = args.next() else {
+                eprintln!("Missing value for {}", flag);
+                break;
+            };
+            match flag.as_str() {
+                "--tick-rate" => config.tick_rate = parse_value(&flag, &value, config.tick_rate).max(1),
+                "--max-rewind-ms" => {
+                    let millis = parse_value(&flag, &value, config.max_rewind.as_millis() as u64);
+                    config.max_rewind = Duration::from_millis(millis);
+                }
+                "--idle-timeout-secs" => {
+                    let secs = parse_value(&flag, &value, config.idle_timeout.as_secs());
+                    config.idle_timeout = Duration::from_secs(secs.max(1));
+                }
+                "--reconnect-grace-secs" => {
+                    let secs = parse_value(&flag, &value, config.reconnect_grace.as_secs());
+                    config.reconnect_grace = Duration::from_secs(secs);
+                }
+                "--view-radius" => config.view_radius = parse_value(&flag, &value, config.view_radius).max(0.0),
+                "--hearing-radius" => config.hearing_radius = parse_value(&flag, &value, config.hearing_radius).max(0.0),
+                "--audible-ms" => {
+                    let millis = parse_value(&flag, &value, config.audible_for.as_millis() as u64);
+                    config.audible_for = Duration::from_millis(millis);
+                }
+                "--move-burst-ms" => {
+                    let millis = parse_value(&flag, &value, config.move_burst.as_millis() as u64);
+                    config.move_burst = Duration::from_millis(millis);
+                }
+                "--kick-after-violations" => {
+                    config.kick_after_violations = parse_value(&flag, &value, config.kick_after_violations);
+                }
+                "--mode" => config.mode = parse_value(&flag, &value, config.mode),
+                "--friendly-fire" => config.friendly_fire = parse_value(&flag, &value, config.friendly_fire),
+                "--min-players" => config.min_players = parse_value(&flag, &value, config.min_players).max(1),
+                "--countdown-secs" => {
+                    let secs = parse_value(&flag, &value, config.countdown.as_secs());
+                    config.countdown = Duration::from_secs(secs);
+                }
+                "--match-secs" => {
+                    let secs = parse_value(&flag, &value, config.match_duration.as_secs());
+                    config.match_duration = Duration::from_secs(secs.max(1));
+                }
+                "--post-match-secs" => {
+                    let secs = parse_value(&flag, &value, config.post_match.as_secs());
+                    config.post_match = Duration::from_secs(secs);
+                }
+                "--respawn-delay-secs" => {
+                    let secs = parse_value(&flag, &value, config.respawn_delay.as_secs());
+                    config.respawn_delay = Duration::from_secs(secs);
+                }
+                "--spawn-protection-ms" => {
+                    let millis = parse_value(&flag, &value, config.spawn_protection.as_millis() as u64);
+                    config.spawn_protection = Duration::from_millis(millis);
+                }
+                _ => eprintln!("Ignoring unknown argument {}", flag),
+            }
+        }
+        config
+    }
+
+    pub fn tick_interval(&self) -> Duration {
+        Duration::from_secs_f64(1.0 / self.tick_rate as f64)
+    }
+
+    pub fn max_rewind_ticks(&self) -> u64 {
+        (self.max_rewind.as_secs_f64() * self.tick_rate as f64).round() as u64
+    }
+}
+
+fn parse_value<T: FromStr>(flag: &str, value: &str, default: T) -> T {
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Invalid value {:?} for {}, keeping the default", value, flag);
+        default
+    })
+}
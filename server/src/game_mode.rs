@@ -0,0 +1,408 @@
+//! Scoring and win conditions of a match, picked with `--mode`.
+//!
+//! The handlers keep track of kills and deaths on each `Player` and ask the
+//! mode how many points a kill is worth, whether dead players come back and
+//! when the match is won. Points, kills and deaths are reset when a match
+//! starts, along with whatever the mode keeps itself.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::time::Duration;
+use maze_wars_protocol::phase::MatchPhase;
+use maze_wars_protocol::scoreboard::{MatchWinner, Scoreboard};
+use maze_wars_protocol::{PlayerId, TeamId};
+use crate::player::Player;
+
+pub type Players = HashMap<SocketAddr, Player>;
+
+pub trait GameMode: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Called before `player` is added to `players`, when they join or come
+    /// back after timing out.
+    fn on_join(&mut self, _player: &mut Player, _players: &Players, _phase: MatchPhase) {}
+
+    fn on_leave(&mut self, _player: &Player) {}
+
+    /// May move players to other teams, the handlers tell everyone.
+    fn on_match_start(&mut self, _players: &mut Players) {}
+
+    /// Points `killer` earns for killing `victim`.
+    fn on_kill(&mut self, killer: &Player, victim: &Player) -> u32;
+
+    /// Called every tick of a match in progress.
+    fn on_tick(&mut self, _players: &Players, _elapsed: Duration) {}
+
+    /// Whether players killed during the match come back after `respawn_delay`.
+    fn respawns(&self) -> bool {
+        true
+    }
+
+    /// The match ends early when this is true, and otherwise when its time
+    /// runs out.
+    fn is_won(&self, _players: &Players) -> bool {
+        false
+    }
+
+    fn winner(&self, players: &Players) -> MatchWinner;
+
+    fn team_scores(&self) -> Vec<(TeamId, u32)> {
+        Vec::new()
+    }
+
+    fn columns(&self) -> &'static [&'static str];
+
+    /// One value per column of `columns`. Players are ranked on these
+    /// values, first column first.
+    fn scoreboard_row(&self, player: &Player) -> Vec<i32>;
+
+    fn scoreboard(&self, players: &Players) -> Scoreboard {
+        let mut rows: Vec<(PlayerId, Vec<i32>)> = players
+            .values()
+            .map(|player| (player.id, self.scoreboard_row(player)))
+            .collect();
+        rows.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        Scoreboard {
+            columns: self.columns().iter().map(|column| column.to_string()).collect(),
+            rows,
+            team_scores: self.team_scores(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameModeKind {
+    FreeForAll,
+    TeamDeathmatch,
+    LastManStanding,
+}
+
+impl GameModeKind {
+    pub fn create(self) -> Box<dyn GameMode> {
+        match self {
+            GameModeKind::FreeForAll => Box::new(FreeForAll),
+            GameModeKind::TeamDeathmatch => Box::<TeamDeathmatch>::default(),
+            GameModeKind::LastManStanding => Box::<LastManStanding>::default(),
+        }
+    }
+}
+
+impl FromStr for GameModeKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ffa" => Ok(GameModeKind::FreeForAll),
+            "tdm" => Ok(GameModeKind::TeamDeathmatch),
+            "lms" => Ok(GameModeKind::LastManStanding),
+            _ => Err(format!("unknown game mode {}, expected ffa, tdm or lms", s)),
+        }
+    }
+}
+
+impl fmt::Display for GameModeKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameModeKind::FreeForAll => write!(f, "ffa"),
+            GameModeKind::TeamDeathmatch => write!(f, "tdm"),
+            GameModeKind::LastManStanding => write!(f, "lms"),
+        }
+    }
+}
+
+const POINTS_PER_KILL: u32 = 10;
+
+/// The player with the most points wins, a tie is a draw.
+fn most_points(players: &Players) -> MatchWinner {
+    let best = players.values().map(|player| player.points).max();
+    let mut leaders = players.values().filter(|player| Some(player.points) == best);
+    match (leaders.next(), leaders.next()) {
+        (Some(leader), None) => MatchWinner::Player(leader.id),
+        _ => MatchWinner::Draw,
+    }
+}
+
+/// Everyone for themselves, the most points when time runs out wins.
+pub struct FreeForAll;
+
+impl GameMode for FreeForAll {
+    fn name(&self) -> &'static str {
+        "Free for all"
+    }
+
+    fn on_kill(&mut self, _killer: &Player, _victim: &Player) -> u32 {
+        POINTS_PER_KILL
+    }
+
+    fn winner(&self, players: &Players) -> MatchWinner {
+        most_points(players)
+    }
+
+    fn columns(&self) -> &'static [&'static str] {
+        &["Points", "Kills", "Deaths"]
+    }
+
+    fn scoreboard_row(&self, player: &Player) -> Vec<i32> {
+        vec![player.points as i32, player.kills as i32, player.deaths as i32]
+    }
+}
+
+const TEAM_COUNT: usize = 2;
+
+fn team_sizes<'a>(players: impl Iterator<Item = &'a Player>) -> [usize; TEAM_COUNT] {
+    let mut sizes = [0; TEAM_COUNT];
+    // Les équipes viennent du serveur, une équipe inconnue n'est pas comptée
+    for team in players.filter_map(|player| player.team) {
+        if let Some(size) = sizes.get_mut(usize::from(team)) {
+            *size += 1;
+        }
+    }
+    sizes
+}
+
+/// Two teams, the one with the most kills of the other when time runs out
+/// wins. Players join the smaller team, or the one behind when sizes are
+/// even, and teams are evened out again when a match starts.
+#[derive(Default)]
+pub struct TeamDeathmatch {
+    kills: [u32; TEAM_COUNT],
+}
+
+impl GameMode for TeamDeathmatch {
+    fn name(&self) -> &'static str {
+        "Team deathmatch"
+    }
+
+    fn on_join(&mut self, player: &mut Player, players: &Players, _phase: MatchPhase) {
+        let sizes = team_sizes(players.values());
+        let team = (0..TEAM_COUNT).min_by_key(|team| (sizes[*team], self.kills[*team])).unwrap_or(0);
+        player.team = Some(team as TeamId);
+    }
+
+    fn on_match_start(&mut self, players: &mut Players) {
+        self.kills = [0; TEAM_COUNT];
+        // Les derniers arrivés changent d'équipe en premier
+        let mut latest: Vec<&mut Player> = players.values_mut().collect();
+        latest.sort_by_key(|player| std::cmp::Reverse(player.id));
+        loop {
+            let sizes = team_sizes(latest.iter().map(|player| &**player));
+            let smallest = (0..TEAM_COUNT).min_by_key(|team| sizes[*team]).unwrap_or(0);
+            let largest = (0..TEAM_COUNT).max_by_key(|team| sizes[*team]).unwrap_or(0);
+            if sizes[largest] - sizes[smallest] <= 1 {
+                break;
+            }
+            if let Some(player) = latest.iter_mut().find(|player| player.team == Some(largest as TeamId)) {
+                player.team = Some(smallest as TeamId);
+            }
+        }
+    }
+
+    fn on_kill(&mut self, killer: &Player, victim: &Player) -> u32 {
+        match killer.team {
+            // Tuer un coéquipier ne rapporte rien
+            Some(team) if !killer.is_teammate(victim) => match self.kills.get_mut(usize::from(team)) {
+                Some(kills) => {
+                    *kills += 1;
+                    POINTS_PER_KILL
+                }
+                None => 0,
+            },
+            _ => 0,
+        }
+    }
+
+    fn winner(&self, _players: &Players) -> MatchWinner {
+        let best = self.kills.iter().max().copied().unwrap_or(0);
+        let mut leaders = (0..TEAM_COUNT).filter(|team| self.kills[*team] == best);
+        match (leaders.next(), leaders.next()) {
+            (Some(team), None) => MatchWinner::Team(team as TeamId),
+            _ => MatchWinner::Draw,
+        }
+    }
+
+    fn team_scores(&self) -> Vec<(TeamId, u32)> {
+        self.kills.iter().enumerate().map(|(team, kills)| (team as TeamId, *kills)).collect()
+    }
+
+    fn columns(&self) -> &'static [&'static str] {
+        &["Team", "Points", "Kills", "Deaths"]
+    }
+
+    fn scoreboard_row(&self, player: &Player) -> Vec<i32> {
+        let team = player.team.map_or(-1, |team| team as i32);
+        vec![team, player.points as i32, player.kills as i32, player.deaths as i32]
+    }
+}
+
+/// No respawns during the match: the last player alive wins. When time runs
+/// out first, the survivor with the most points does.
+#[derive(Default)]
+pub struct LastManStanding {
+    survived: HashMap<PlayerId, Duration>,
+}
+
+impl GameMode for LastManStanding {
+    fn name(&self) -> &'static str {
+        "Last man standing"
+    }
+
+    fn on_join(&mut self, player: &mut Player, _players: &Players, phase: MatchPhase) {
+        // Arrivé en cours de match : spectateur jusqu'au suivant
+        if phase == MatchPhase::InProgress {
+            player.is_alive = false;
+        }
+    }
+
+    fn on_match_start(&mut self, _players: &mut Players) {
+        self.survived.clear();
+    }
+
+    fn on_kill(&mut self, _killer: &Player, _victim: &Player) -> u32 {
+        POINTS_PER_KILL
+    }
+
+    fn on_tick(&mut self, players: &Players, elapsed: Duration) {
+        for player in players.values().filter(|player| player.is_alive) {
+            *self.survived.entry(player.id).or_default() += elapsed;
+        }
+    }
+
+    fn respawns(&self) -> bool {
+        false
+    }
+
+    fn is_won(&self, players: &Players) -> bool {
+        players.values().filter(|player| player.is_alive).count() <= 1
+    }
+
+    fn winner(&self, players: &Players) -> MatchWinner {
+        let survivors: Players = players
+            .iter()
+            .filter(|(_, player)| player.is_alive)
+            .map(|(addr, player)| (*addr, player.clone()))
+            .collect();
+        most_points(&survivors)
+    }
+
+    fn columns(&self) -> &'static [&'static str] {
+        &["Alive", "Points", "Kills", "Survived (s)"]
+    }
+
+    fn scoreboard_row(&self, player: &Player) -> Vec<i32> {
+        let survived = self.survived.get(&player.id).copied().unwrap_or_default();
+        vec![player.is_alive as i32, player.points as i32, player.kills as i32, survived.as_secs() as i32]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn join(mode: &mut dyn GameMode, players: &mut Players, id: PlayerId, phase: MatchPhase) {
+        let mut player = Player::for_tests(id);
+        mode.on_join(&mut player, players, phase);
+        players.insert(SocketAddr::from(([127, 0, 0, 1], 1000 + id as u16)), player);
+    }
+
+    fn get(players: &Players, id: PlayerId) -> &Player {
+        players.values().find(|player| player.id == id).unwrap()
+    }
+
+    #[test]
+    fn free_for_all_ties_are_draws() {
+        let mut mode = FreeForAll;
+        let mut players = Players::new();
+        for id in 1..=3 {
+            join(&mut mode, &mut players, id, MatchPhase::InProgress);
+        }
+        for (points, player) in [20, 20, 10].into_iter().zip(players.values_mut()) {
+            player.points = points;
+        }
+        assert_eq!(mode.winner(&players), MatchWinner::Draw);
+
+        players.values_mut().find(|player| player.points == 10).unwrap().points = 30;
+        let leader = players.values().find(|player| player.points == 30).unwrap().id;
+        assert_eq!(mode.winner(&players), MatchWinner::Player(leader));
+        assert_eq!(mode.scoreboard(&players).rows[0].0, leader);
+    }
+
+    #[test]
+    fn team_deathmatch_balances_teams_and_ignores_team_kills() {
+        let mut mode = TeamDeathmatch::default();
+        let mut players = Players::new();
+        for id in 1..=4 {
+            join(&mut mode, &mut players, id, MatchPhase::WaitingForPlayers);
+        }
+        assert_eq!(team_sizes(players.values()), [2, 2]);
+
+        // Le remplaçant d'un joueur parti rejoint son équipe
+        let team = get(&players, 1).team;
+        players.retain(|_, player| player.id != 1);
+        join(&mut mode, &mut players, 5, MatchPhase::InProgress);
+        assert_eq!(get(&players, 5).team, team);
+
+        let team_of = |players: &Players, id| get(players, id).team;
+        let (ally, enemy) = if team_of(&players, 2) == team_of(&players, 3) { (3, 4) } else { (4, 3) };
+        assert_eq!(mode.on_kill(get(&players, 2), get(&players, ally)), 0);
+        assert_eq!(mode.on_kill(get(&players, 2), get(&players, enemy)), POINTS_PER_KILL);
+        let winner = team_of(&players, 2).unwrap();
+        assert_eq!(mode.winner(&players), MatchWinner::Team(winner));
+        assert_eq!(mode.scoreboard(&players).team_scores, vec![(0, (winner == 0) as u32), (1, (winner == 1) as u32)]);
+    }
+
+    #[test]
+    fn team_deathmatch_ignores_unknown_teams() {
+        let mut mode = TeamDeathmatch::default();
+        let mut players = Players::new();
+        for id in 1..=2 {
+            join(&mut mode, &mut players, id, MatchPhase::InProgress);
+        }
+        players.values_mut().for_each(|player| player.team = Some(player.id as TeamId + 5));
+        assert_eq!(team_sizes(players.values()), [0, 0]);
+        assert_eq!(mode.on_kill(get(&players, 1), get(&players, 2)), 0);
+        assert_eq!(mode.winner(&players), MatchWinner::Draw);
+    }
+
+    #[test]
+    fn team_deathmatch_evens_out_teams_at_match_start() {
+        let mut mode = TeamDeathmatch::default();
+        let mut players = Players::new();
+        for id in 1..=5 {
+            join(&mut mode, &mut players, id, MatchPhase::WaitingForPlayers);
+        }
+        // Tout un côté s'en va
+        let leaving = get(&players, 1).team;
+        players.retain(|_, player| player.team != leaving);
+        assert_eq!(team_sizes(players.values()).iter().sum::<usize>(), players.len());
+        mode.on_match_start(&mut players);
+        let sizes = team_sizes(players.values());
+        assert!(sizes[0].abs_diff(sizes[1]) <= 1, "{:?}", sizes);
+        // Le plus ancien garde son équipe
+        let oldest = players.values().map(|player| player.id).min().unwrap();
+        assert_ne!(get(&players, oldest).team, leaving);
+    }
+
+    #[test]
+    fn last_man_standing_ends_with_one_survivor() {
+        let mut mode = LastManStanding::default();
+        let mut players = Players::new();
+        for id in 1..=3 {
+            join(&mut mode, &mut players, id, MatchPhase::Countdown);
+        }
+        mode.on_match_start(&mut players);
+        assert!(!mode.respawns());
+        assert!(!mode.is_won(&players));
+
+        // Arrivé trop tard pour ce match
+        join(&mut mode, &mut players, 4, MatchPhase::InProgress);
+        assert!(!get(&players, 4).is_alive);
+
+        for player in players.values_mut().filter(|player| player.id != 2) {
+            player.is_alive = false;
+        }
+        assert!(mode.is_won(&players));
+        assert_eq!(mode.winner(&players), MatchWinner::Player(2));
+    }
+}
@@ -1,32 +1,150 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::time::{Instant, Duration};
-use crate::map::Map;
-use crate::player::Player;
+use std::time::{Duration, Instant};
+use maze_wars_protocol::map_transfer::{self, MapInfo};
+use maze_wars_protocol::phase::{MatchClock, MatchPhase};
+use maze_wars_protocol::{Map, PlayerId, SessionToken, TeamId};
+use crate::config::ServerConfig;
+use crate::game_mode::GameMode;
+use crate::lag_compensation::PositionHistory;
+use crate::map::{generate_map, generate_safe_spawn_point};
+use crate::player::{are_teammates, Player};
 
 pub struct GameState {
     pub players: HashMap<SocketAddr, Player>,
     pub map: Map,
+    pub map_info: MapInfo,
+    pub map_chunks: Vec<Vec<u8>>, // compressed map, sent after every Welcome
     pub difficulty: u8,
-    pub game_start_time: Instant,
-    pub game_duration: Duration,
-    pub rotation: f32,
+    pub started: Instant, // when the server started, see `clock`
+    pub mode: Box<dyn GameMode>, // scoring and win conditions
+    pub phase: MatchPhase,
+    pub phase_started: Instant,
+    pub tick: u64,
+    pub history: PositionHistory,
+    pub config: ServerConfig,
+    pub next_player_id: PlayerId,
+    pub disconnected: HashMap<SessionToken, (Player, Instant)>, // timed out players that may still reconnect
 }
 
 impl GameState {
-    pub fn new(difficulty: u8) -> Self {
+    pub fn new(difficulty: u8, config: ServerConfig) -> Self {
+        let map = generate_map(difficulty);
+        let (map_info, map_chunks) = map_transfer::split(&map, rand::random());
         Self {
             players: HashMap::new(),
-            map: Map::new(difficulty),
+            map,
+            map_info,
+            map_chunks,
             difficulty,
-            game_start_time: Instant::now(),
-            game_duration: Duration::from_secs(300), // 5 minutes
-            rotation: 0.0,
+            started: Instant::now(),
+            mode: config.mode.create(),
+            phase: MatchPhase::WaitingForPlayers,
+            phase_started: Instant::now(),
+            tick: 0,
+            history: PositionHistory::default(),
+            config,
+            next_player_id: 1,
+            disconnected: HashMap::new(),
         }
     }
 
-    pub fn is_game_over(&self) -> bool {
-        self.game_start_time.elapsed() >= self.game_duration
+    pub fn allocate_player_id(&mut self) -> PlayerId {
+        let id = self.next_player_id;
+        self.next_player_id += 1;
+        id
     }
-    
-}
\ No newline at end of file
+
+    pub fn find_session(&self, session: SessionToken) -> Option<SocketAddr> {
+        self.players.iter().find(|(_, player)| player.session == session).map(|(addr, _)| *addr)
+    }
+
+    /// `name`, or `name (2)`, `name (3)`... when it is already taken.
+    pub fn unique_name(&self, name: &str) -> String {
+        let taken = |candidate: &str| self.players.values().any(|player| player.name == candidate);
+        if !taken(name) {
+            return name.to_string();
+        }
+        (2..)
+            .map(|n| format!("{} ({})", name, n))
+            .find(|candidate| !taken(candidate))
+            .unwrap()
+    }
+
+    /// Seconds since the server started, the clock of every player's `Loadout`.
+    pub fn clock(&self) -> f64 {
+        self.started.elapsed().as_secs_f64()
+    }
+
+    /// How long the current phase lasts, None while waiting for players.
+    pub fn phase_duration(&self) -> Option<Duration> {
+        match self.phase {
+            MatchPhase::WaitingForPlayers => None,
+            MatchPhase::Countdown => Some(self.config.countdown),
+            MatchPhase::InProgress => Some(self.config.match_duration),
+            MatchPhase::PostMatch => Some(self.config.post_match),
+        }
+    }
+
+    pub fn match_clock(&self) -> MatchClock {
+        let remaining = self.phase_duration().map_or(Duration::ZERO, |duration| duration.saturating_sub(self.phase_started.elapsed()));
+        MatchClock {
+            phase: self.phase,
+            remaining_ms: remaining.as_millis().min(u32::MAX as u128) as u32,
+        }
+    }
+
+    /// Generates the map of the next match. Clients get it like after
+    /// `Welcome` and must acknowledge it again, timed out players too when
+    /// they come back.
+    pub fn new_map(&mut self) {
+        self.map = generate_map(self.difficulty);
+        (self.map_info, self.map_chunks) = map_transfer::split(&self.map, rand::random());
+        let parked = self.disconnected.values_mut().map(|(player, _)| player);
+        for player in self.players.values_mut().chain(parked) {
+            player.map_loaded = false;
+        }
+    }
+
+    /// A player back from `disconnected` keeps their position, unless they
+    /// never loaded the current map: it may be a spot of the previous one.
+    pub fn place_resumed(&self, player: &mut Player) {
+        if !player.map_loaded {
+            player.position = self.safe_spawn_point(player.id, player.team);
+        }
+    }
+
+    /// A spawn point for player `id` of `team`, hidden from the living
+    /// players of other teams.
+    pub fn safe_spawn_point(&self, id: PlayerId, team: Option<TeamId>) -> (f32, f32) {
+        let (teammates, enemies): (Vec<&Player>, Vec<&Player>) = self.players
+            .values()
+            .filter(|player| player.id != id && player.is_alive)
+            .partition(|player| are_teammates(player.team, team));
+        let positions = |players: Vec<&Player>| players.iter().map(|player| player.position).collect::<Vec<_>>();
+        generate_safe_spawn_point(&self.map, &self.config, &positions(enemies), &positions(teammates))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maze_wars_protocol::map::is_valid_move;
+
+    #[test]
+    fn players_resuming_after_a_new_map_load_it_again() {
+        let mut state = GameState::new(2, ServerConfig::default());
+        // Dans un mur : la position n'a de sens que sur l'ancienne carte
+        let mut parked = Player::for_tests(1);
+        parked.position = (0.0, 0.0);
+        state.disconnected.insert(parked.session, (parked, Instant::now()));
+        state.players.insert(SocketAddr::from(([127, 0, 0, 1], 1002)), Player::for_tests(2));
+
+        state.new_map();
+        assert!(state.players.values().all(|player| !player.map_loaded));
+        let (mut player, _) = state.disconnected.remove(&1).unwrap();
+        assert!(!player.map_loaded);
+        state.place_resumed(&mut player);
+        assert!(is_valid_move(&state.map, player.position.0, player.position.1), "{:?}", player.position);
+    }
+}
@@ -1,89 +1,195 @@
-use tokio::net::UdpSocket;
 use std::collections::HashMap;
-use std::sync::Arc;
-use tokio::sync::Mutex;
 use std::net::SocketAddr;
-use std::time::Duration;
+use std::time::Instant;
+use rand::Rng;
+use maze_wars_protocol::damage::{self, MAX_HEALTH, SPAWN_ARMOR};
+use maze_wars_protocol::movement::apply_move;
+use maze_wars_protocol::phase::MatchPhase;
+use maze_wars_protocol::weapon::Loadout;
+use maze_wars_protocol::snapshot::{self, PackedPlayer, PackedWorld};
+use maze_wars_protocol::{ClientMessage, PlayerId, PlayerSnapshot, ServerMessage, SessionToken, TeamId};
 use crate::game_state::GameState;
-use crate::messages::{ClientMessage, ServerMessage};
-use crate::player::{Player, PLAYER_SPEED, SHOOT_RANGE};
-use crate::map::is_valid_move;
+use crate::map::generate_safe_spawn_point;
+use crate::network::Transport;
+use crate::player::{are_teammates, Player};
+use crate::snapshots::SentSnapshots;
+use crate::validation::{self, MoveBudget, Violation, Violations, FIRE_LEEWAY};
+use crate::visibility;
 
 pub async fn handle_message(
     message: ClientMessage,
     addr: SocketAddr,
-    game_state: Arc<Mutex<GameState>>,
-    socket: Arc<UdpSocket>,
+    state: &mut GameState,
+    transport: &mut Transport,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let mut state = game_state.lock().await;
     match message {
         ClientMessage::Join { name } => {
-            println!("Player connected: {} (IP: {})", name, addr);
-            let spawn_position = state.map.generate_valid_spawn_point();
-            let player = Player {
-                name: name.clone(),
-                position: spawn_position,
-                rotation: 0.0,  // Ajoutez une rotation initiale
-                is_alive: true,
-                points: 0
-            };
-            state.players.insert(addr, player);
-            let welcome_message = ServerMessage::Welcome {
-                map: state.map.clone(),
-                player_id: name,
-                difficulty: state.difficulty,
-            };
-            let serialized = serde_json::to_string(&welcome_message)?;
-            socket.send_to(serialized.as_bytes(), addr).await?;
-            println!("Sent Welcome message to new player");
-            broadcast_game_state(&state, &socket).await?;
-        }
-        ClientMessage::Move { direction } => {
-            let mut new_position = None;
-            let mut new_rotation = None;
-            
-            if let Some(player) = state.players.get(&addr) {
-                let new_x = player.position.0 + direction.0 * PLAYER_SPEED;
-                let new_y = player.position.1 + direction.1 * PLAYER_SPEED;
-                
-                if is_valid_move(&state.map, new_x, new_y) {
-                    new_position = Some((new_x, new_y));
+            if state.players.contains_key(&addr) {
+                // Le joueur est déjà dans la partie, lui renvoyer son identité
+                send_welcome(addr, state, transport).await;
+                return Ok(());
+            }
+            join(name, addr, state, transport).await;
+        }
+        ClientMessage::Reconnect { session, name } => {
+            // L'adresse a déjà été réassociée au joueur si sa session était encore active
+            // Le client repart de zéro : les snapshots envoyés avant ne peuvent plus servir de base
+            if let Some(player) = state.players.get_mut(&addr).filter(|player| player.session == session) {
+                println!("Player {} reconnected", player.name);
+                player.snapshots.clear();
+                send_welcome(addr, state, transport).await;
+            } else if let Some((mut player, _)) = state.disconnected.remove(&session) {
+                println!("Player {} reconnected after timing out (IP: {})", player.name, addr);
+                player.name = state.unique_name(&player.name);
+                player.last_seen = Instant::now();
+                player.snapshots.clear();
+                state.mode.on_join(&mut player, &state.players, state.phase);
+                // Nouvelle carte depuis le départ : send_welcome la renvoie, les snapshots attendent l'accusé
+                state.place_resumed(&mut player);
+                let joined_message = ServerMessage::PlayerJoined { player: player.id, name: player.name.clone(), team: player.team };
+                state.players.insert(addr, player);
+                for other_addr in state.players.keys() {
+                    if *other_addr != addr {
+                        transport.send(*other_addr, &joined_message).await;
+                    }
                 }
-
-                // Calculer la nouvelle rotation basée sur la direction du mouvement
-                if direction.0 != 0.0 || direction.1 != 0.0 {
-                    new_rotation = Some(direction.1.atan2(direction.0));
+                send_welcome(addr, state, transport).await;
+            } else {
+                println!("Unknown or expired session from {}, joining as a new player", addr);
+                join(name, addr, state, transport).await;
+            }
+        }
+        ClientMessage::MapReceived { map_id } => {
+            if let Some(player) = state.players.get_mut(&addr) {
+                if map_id == state.map_info.id && !player.map_loaded {
+                    println!("Player {} loaded the map", player.name);
+                    player.map_loaded = true;
                 }
             }
-            
-            if let Some(new_pos) = new_position {
-                if let Some(player) = state.players.get_mut(&addr) {
-                    player.position = new_pos;
-                    if let Some(rot) = new_rotation {
-                        player.rotation = rot;
+        }
+        ClientMessage::Heartbeat => {}
+        ClientMessage::Leave => {
+            if let Some(player) = state.players.remove(&addr) {
+                println!("Player {} left the game", player.name);
+                state.mode.on_leave(&player);
+                broadcast_player_left(state, transport, player.id).await?;
+            }
+        }
+        ClientMessage::Move { sequence, direction } => {
+            let others: Vec<(f32, f32)> = state.players.iter()
+                .filter(|(other, player)| **other != addr && player.is_alive)
+                .map(|(_, player)| player.position)
+                .collect();
+            let map = &state.map;
+            if let Some(player) = state.players.get_mut(&addr) {
+                // Les Move ne sont pas fiables : ignorer les doublons et ceux arrivés en retard
+                if sequence <= player.last_input {
+                    return Ok(());
+                }
+                // Appliquée ou rejetée, le client ne doit plus rejouer cette entrée
+                player.last_input = sequence;
+                if !player.is_alive {
+                    return Ok(());
+                }
+
+                let checked = validation::sanitize_direction(direction)
+                    .and_then(|direction| player.move_budget.spend_move().map(|_| direction));
+                let direction = match checked {
+                    Ok(direction) => direction,
+                    Err(violation) => return reject_input(addr, state, transport, violation).await,
+                };
+                player.violations.clear();
+
+                let new_position = apply_move(map, player.position, direction, &others);
+                if new_position != player.position {
+                    player.position = new_position;
+                    // Calculer la nouvelle rotation basée sur la direction du mouvement
+                    if direction.0 != 0.0 || direction.1 != 0.0 {
+                        player.rotation = direction.1.atan2(direction.0);
                     }
                 }
             }
         }
-        ClientMessage::Shoot { direction } => {
+        ClientMessage::Reload { weapon } => {
+            let clock = state.clock();
+            let Some(player) = state.players.get_mut(&addr) else {
+                return Ok(());
+            };
+            if !player.loadout.switch(weapon) {
+                return reject_input(addr, state, transport, Violation::UnknownWeapon).await;
+            }
+            player.violations.clear();
+            // Chargeur plein ou réserve vide : le client l'apprendra par le prochain snapshot
+            if player.is_alive && player.loadout.reload(clock) {
+                println!("Player {} is reloading", player.name);
+            }
+        }
+        ClientMessage::Shoot { weapon, direction, pitch, view_tick } => {
+            let now = Instant::now();
+            let clock = state.clock();
+            let Some(shooter) = state.players.get_mut(&addr) else {
+                return Ok(());
+            };
+            // Pas de tirs avant le début du match ni pendant les scores
+            if !shooter.is_alive || state.phase != MatchPhase::InProgress {
+                return Ok(());
+            }
+            if !pitch.is_finite() {
+                return reject_input(addr, state, transport, Violation::InvalidPitch).await;
+            }
+            if !shooter.loadout.switch(weapon) {
+                return reject_input(addr, state, transport, Violation::UnknownWeapon).await;
+            }
+            let weapon = match shooter.loadout.fire(clock, FIRE_LEEWAY) {
+                Ok(weapon) => weapon,
+                Err(error) => return reject_input(addr, state, transport, error.into()).await,
+            };
+            shooter.violations.clear();
+            shooter.last_shot = Some(now);
+            // Tirer met fin à la protection
+            shooter.protected_until = None;
+
             let shooter = state.players.get(&addr).cloned();
             if let Some(shooter) = shooter {
-                println!("Player {} is shooting!", shooter.name);
+                println!("Player {} is shooting with {}!", shooter.name, weapon.name);
                 
                 let start_pos = shooter.position;
-                
+                // La direction vient de la caméra : sans tangage, elle n'est plus unitaire
+                let length = (direction.0 * direction.0 + direction.1 * direction.1).sqrt();
+                if length == 0.0 || !length.is_finite() {
+                    return Ok(());
+                }
+                // Dispersion de l'arme, tirée par le serveur
+                let deviation = rand::thread_rng().gen_range(-weapon.spread..=weapon.spread);
+                let (sin, cos) = deviation.sin_cos();
+                let direction = (direction.0 / length, direction.1 / length);
+                let direction = (direction.0 * cos - direction.1 * sin, direction.0 * sin + direction.1 * cos);
+
+                // Aucun joueur ne peut être touché derrière le premier mur
+                let wall_hit = state.map.raycast(start_pos, direction, weapon.range);
+                let max_distance = wall_hit.map_or(weapon.range, |hit| hit.distance);
+
+                // Remonter au tick que le tireur voyait, dans la limite configurée
+                let rewind = state.tick.saturating_sub(view_tick).min(state.config.max_rewind_ticks());
+                let past_positions = state.history.positions_at(state.tick - rewind);
+
                 let mut hit_player = None;
                 let mut closest_distance = f32::MAX;
                 
                 for (player_addr, player) in state.players.iter() {
-                    if player_addr != &addr && player.is_alive {
-                        let player_pos = player.position;
+                    // Sans tir ami, les balles traversent les coéquipiers
+                    let shielded = player.is_protected(now) || (!state.config.friendly_fire && player.is_teammate(&shooter));
+                    if player_addr != &addr && player.is_alive && !shielded {
+                        let player_pos = past_positions
+                            .and_then(|positions| positions.get(&player.id))
+                            .copied()
+                            .unwrap_or(player.position);
                         
                         // Calculer la distance du joueur à la ligne de tir
                         let to_player = (player_pos.0 - start_pos.0, player_pos.1 - start_pos.1);
                         let dot_product = to_player.0 * direction.0 + to_player.1 * direction.1;
                         
-                        if dot_product > 0.0 && dot_product < SHOOT_RANGE {
+                        if dot_product > 0.0 && dot_product < max_distance {
                             let closest_point = (
                                 start_pos.0 + direction.0 * dot_product,
                                 start_pos.1 + direction.1 * dot_product
@@ -93,92 +199,458 @@ pub async fn handle_message(
                             
                             if distance < 0.2 { // Augmenté pour tenir compte de la taille du modèle
                                 let player_distance = ((player_pos.0 - start_pos.0).powi(2) + (player_pos.1 - start_pos.1).powi(2)).sqrt();
-                                if player_distance < closest_distance {
+                                // Une balle qui passe au-dessus de la tête peut toucher quelqu'un derrière
+                                let zone = damage::hit_zone(player_distance, pitch);
+                                if let Some(zone) = zone.filter(|_| player_distance < closest_distance) {
                                     closest_distance = player_distance;
-                                    hit_player = Some((player_addr.clone(), player.name.clone()));
+                                    hit_player = Some((*player_addr, player.id, player.name.clone(), player_pos, zone));
                                 }
                             }
                         }
                     }
                 }
                 
-                if let Some((hit_addr, hit_name)) = hit_player {
-                    if let Some(player) = state.players.get_mut(&hit_addr) {
-                        player.is_alive = false;
-                    }
-                    if let Some(shooter) = state.players.get_mut(&addr) {
-                        shooter.points += 10;
+                let (impact, hit_wall) = match (&hit_player, wall_hit) {
+                    (Some((_, _, _, player_pos, _)), _) => (*player_pos, false),
+                    (None, Some(hit)) => (hit.point, true),
+                    (None, None) => ((start_pos.0 + direction.0 * weapon.range, start_pos.1 + direction.1 * weapon.range), false),
+                };
+                // Un impact sur un joueur donne sa position exacte : seuls le tireur et la cible la reçoivent.
+                // Sinon, ceux qui entendent le tireur ou voient l'impact
+                let fired_message = ServerMessage::ShotFired { shooter: shooter.id, impact, hit_wall };
+                for (viewer_addr, viewer) in state.players.iter() {
+                    let told = match &hit_player {
+                        Some((hit_addr, ..)) => *viewer_addr == addr || viewer_addr == hit_addr,
+                        None => visibility::is_relevant(&state.map, &state.config, viewer, &shooter)
+                            || visibility::can_see(&state.map, &state.config, viewer.position, impact),
+                    };
+                    if told {
+                        transport.send(*viewer_addr, &fired_message).await;
                     }
-                    
-                    let shot_message = ServerMessage::PlayerShot { 
-                        shooter: shooter.name.clone(),
-                        target: hit_name.clone(),
+                }
+
+                if let Some((hit_addr, hit_id, hit_name, _, zone)) = hit_player {
+                    let damage = damage::shot_damage(weapon, closest_distance, zone);
+                    let Some(target) = state.players.get_mut(&hit_addr) else {
+                        return Ok(());
                     };
-                    let serialized = serde_json::to_string(&shot_message)?;
-                    socket.send_to(serialized.as_bytes(), &hit_addr).await?;
-                    
-                    let death_message = ServerMessage::PlayerDied { 
-                        player: hit_name.clone()
+                    (target.health, target.armor) = damage::apply_damage(target.health, target.armor, damage);
+                    let (health, armor) = (target.health, target.armor);
+                    let killed = health == 0;
+                    if killed {
+                        target.is_alive = false;
+                        target.died_at = Some(now);
+                    }
+
+                    let shot_message = ServerMessage::PlayerShot {
+                        shooter: shooter.id,
+                        target: hit_id,
+                        zone,
+                        damage,
+                        health,
+                        armor,
+                        from: start_pos,
                     };
-                    let serialized = serde_json::to_string(&death_message)?;
-                    for addr in state.players.keys() {
-                        socket.send_to(serialized.as_bytes(), addr).await?;
+                    transport.send(hit_addr, &shot_message).await;
+                    transport.send(addr, &shot_message).await;
+                    println!("Player {} hit {} ({:?}) for {} damage, {} health left", shooter.name, hit_name, zone, damage, health);
+
+                    if killed {
+                        let points = match state.players.get(&hit_addr) {
+                            Some(victim) => state.mode.on_kill(&shooter, victim),
+                            None => 0,
+                        };
+                        let team_kill = state.players.get(&hit_addr).is_some_and(|victim| victim.is_teammate(&shooter));
+                        if let Some(shooter) = state.players.get_mut(&addr) {
+                            shooter.points += points;
+                            // Un coéquipier tué ne compte pas comme un kill
+                            if !team_kill {
+                                shooter.kills += 1;
+                            }
+                        }
+                        if let Some(victim) = state.players.get_mut(&hit_addr) {
+                            victim.deaths += 1;
+                        }
+                        let death_message = ServerMessage::PlayerDied {
+                            player: hit_id
+                        };
+                        for addr in state.players.keys() {
+                            transport.send(*addr, &death_message).await;
+                        }
+                        println!("Player {} was shot and killed by {}!", hit_name, shooter.name);
                     }
-                    
-                    println!("Player {} was shot and killed by {}!", hit_name, shooter.name);
                 } else {
                     println!("Player {} missed their shot!", shooter.name);
                 }
             }
         }
     }
-    broadcast_game_state(&state, &socket).await?;
     Ok(())
 }
 
-pub async fn broadcast_game_state(
-    state: &GameState,
-    socket: &Arc<UdpSocket>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let players_state: HashMap<String, (f32, f32, f32, bool)> = state.players
+async fn join(name: String, addr: SocketAddr, state: &mut GameState, transport: &mut Transport) {
+    let name = state.unique_name(&name);
+    let id = state.allocate_player_id();
+    println!("Player connected: {} #{} (IP: {})", name, id, addr);
+    let mut player = Player {
+        id,
+        name: name.clone(),
+        session: rand::random(),
+        position: (0.0, 0.0), // une fois l'équipe connue
+        team: None,
+        rotation: 0.0,  // Ajoutez une rotation initiale
+        is_alive: true,
+        points: 0,
+        kills: 0,
+        deaths: 0,
+        last_input: 0,
+        last_seen: Instant::now(),
+        last_shot: None,
+        map_loaded: false,
+        snapshots: SentSnapshots::default(),
+        move_budget: MoveBudget::full(state.config.move_burst),
+        violations: Violations::default(),
+        loadout: Loadout::default(),
+        health: MAX_HEALTH,
+        armor: SPAWN_ARMOR,
+        died_at: None,
+        protected_until: Some(Instant::now() + state.config.spawn_protection),
+    };
+    state.mode.on_join(&mut player, &state.players, state.phase);
+    player.position = state.safe_spawn_point(id, player.team);
+    let (is_alive, team) = (player.is_alive, player.team);
+    state.players.insert(addr, player);
+
+    // Présenter le nouveau joueur aux autres
+    let joined_message = ServerMessage::PlayerJoined { player: id, name, team };
+    for other_addr in state.players.keys() {
+        if *other_addr != addr {
+            transport.send(*other_addr, &joined_message).await;
+        }
+    }
+    send_welcome(addr, state, transport).await;
+    println!("Sent Welcome message to new player");
+    // Le mode peut faire attendre le prochain match
+    if !is_alive {
+        let death_message = ServerMessage::PlayerDied { player: id };
+        for addr in state.players.keys() {
+            transport.send(*addr, &death_message).await;
+        }
+    }
+}
+
+/// Sends the player at `addr` their identity, the name of every player,
+/// themselves included, the map and the phase of the match.
+async fn send_welcome(addr: SocketAddr, state: &GameState, transport: &mut Transport) {
+    let Some(player) = state.players.get(&addr) else {
+        return;
+    };
+    let welcome_message = ServerMessage::Welcome {
+        player_id: player.id,
+        name: player.name.clone(),
+        session: player.session,
+        difficulty: state.difficulty,
+    };
+    transport.send(addr, &welcome_message).await;
+    for other in state.players.values() {
+        let joined_message = ServerMessage::PlayerJoined { player: other.id, name: other.name.clone(), team: other.team };
+        transport.send(addr, &joined_message).await;
+    }
+    send_map(addr, state, transport).await;
+    transport.send(addr, &ServerMessage::PhaseChanged { phase: state.phase }).await;
+}
+
+async fn send_map(addr: SocketAddr, state: &GameState, transport: &mut Transport) {
+    // La carte ne tient pas dans un seul datagramme
+    for (index, data) in state.map_chunks.iter().enumerate() {
+        let chunk_message = ServerMessage::MapChunk { info: state.map_info, index: index as u16, data: data.clone() };
+        transport.send(addr, &chunk_message).await;
+    }
+}
+
+/// Moves the player whose session is `session` to `addr`, when a packet
+/// shows they are now reachable there (new network, expired NAT mapping).
+pub fn rebind_session(session: SessionToken, addr: SocketAddr, state: &mut GameState, transport: &mut Transport) {
+    let Some(old_addr) = state.find_session(session) else {
+        return;
+    };
+    if old_addr == addr || state.players.contains_key(&addr) {
+        return;
+    }
+    if let Some(player) = state.players.remove(&old_addr) {
+        println!("Player {} moved from {} to {}", player.name, old_addr, addr);
+        state.players.insert(addr, player);
+        transport.rebind(old_addr, addr);
+    }
+}
+
+/// Advances everything that does not depend on client input.
+pub async fn step(state: &mut GameState, transport: &mut Transport) -> Result<(), Box<dyn std::error::Error>> {
+    state.tick += 1;
+    // Le déplacement autorisé dépend du temps écoulé, pas du nombre de paquets
+    let (elapsed, burst) = (state.config.tick_interval(), state.config.move_burst);
+    for player in state.players.values_mut() {
+        player.move_budget.refill(elapsed, burst);
+    }
+    let positions = state.players.values().map(|player| (player.id, player.position)).collect();
+    let capacity = state.config.max_rewind_ticks() as usize + 1;
+    state.history.record(state.tick, positions, capacity);
+    evict_idle_players(state, transport).await?;
+    if state.phase == MatchPhase::InProgress {
+        state.mode.on_tick(&state.players, elapsed);
+    }
+    respawn_players(state, transport).await?;
+    advance_phase(state, transport).await?;
+    // Oublier les adresses qui ne correspondent à aucun joueur
+    transport.retain(|addr| state.players.contains_key(addr));
+    Ok(())
+}
+
+async fn evict_idle_players(state: &mut GameState, transport: &mut Transport) -> Result<(), Box<dyn std::error::Error>> {
+    let timeout = state.config.idle_timeout;
+    let idle: Vec<SocketAddr> = state.players
+        .iter()
+        .filter(|(_, player)| player.last_seen.elapsed() >= timeout)
+        .map(|(addr, _)| *addr)
+        .collect();
+    for addr in idle {
+        if let Some(player) = state.players.remove(&addr) {
+            println!("Player {} timed out", player.name);
+            state.mode.on_leave(&player);
+            broadcast_player_left(state, transport, player.id).await?;
+            // Garder le joueur un moment au cas où il se reconnecte avec sa session
+            state.disconnected.insert(player.session, (player, Instant::now()));
+        }
+    }
+    let grace = state.config.reconnect_grace;
+    state.disconnected.retain(|_, (_, since)| since.elapsed() < grace);
+    Ok(())
+}
+
+async fn respawn_players(state: &mut GameState, transport: &mut Transport) -> Result<(), Box<dyn std::error::Error>> {
+    // Certains modes ne font revenir les morts qu'au match suivant
+    if state.phase == MatchPhase::InProgress && !state.mode.respawns() {
+        return Ok(());
+    }
+    let delay = state.config.respawn_delay;
+    let ready: Vec<SocketAddr> = state.players
         .iter()
-        .map(|(_, player)| (player.name.clone(), (player.position.0, player.position.1, player.rotation, player.is_alive)))
+        .filter(|(_, player)| !player.is_alive && player.died_at.is_some_and(|died_at| died_at.elapsed() >= delay))
+        .map(|(addr, _)| *addr)
         .collect();
+    for addr in ready {
+        let Some((id, team)) = state.players.get(&addr).map(|player| (player.id, player.team)) else {
+            continue;
+        };
+        let position = state.safe_spawn_point(id, team);
+        let protected_until = Instant::now() + state.config.spawn_protection;
+        if let Some(player) = state.players.get_mut(&addr) {
+            player.position = position;
+            player.is_alive = true;
+            player.health = MAX_HEALTH;
+            player.armor = SPAWN_ARMOR;
+            player.loadout = Loadout::default();
+            player.died_at = None;
+            player.protected_until = Some(protected_until);
+            println!("Player {} respawned", player.name);
+        }
+        send_respawn(addr, state, transport).await;
+    }
+    Ok(())
+}
 
-    let game_state_message = ServerMessage::GameState { players: players_state.clone() };
-    let serialized = serde_json::to_string(&game_state_message)?;
-    
-    println!("Broadcasting GameState:");
-    for (name, (x, y, _, is_alive)) in &players_state {
-        println!("  Player: {}, Position: ({}, {}), Alive: {}", name, x, y, is_alive);
+/// Tells the viewers who can see or hear the player at `addr` where they
+/// respawned. The others only learn of it from their snapshots, once the
+/// player becomes relevant to them.
+async fn send_respawn(addr: SocketAddr, state: &GameState, transport: &mut Transport) {
+    let Some(respawned) = state.players.get(&addr) else {
+        return;
+    };
+    let respawned_message = ServerMessage::PlayerRespawned { player: respawned.id, position: respawned.position };
+    for (viewer_addr, viewer) in state.players.iter() {
+        if visibility::is_relevant(&state.map, &state.config, viewer, respawned) {
+            transport.send(*viewer_addr, &respawned_message).await;
+        }
     }
-    
+}
+
+/// Drops an input that failed validation, and kicks its sender once they
+/// have made too many such mistakes in a row.
+async fn reject_input(addr: SocketAddr, state: &mut GameState, transport: &mut Transport, violation: Violation) -> Result<(), Box<dyn std::error::Error>> {
+    let Some(player) = state.players.get_mut(&addr) else {
+        return Ok(());
+    };
+    let count = player.violations.record();
+    println!("Rejected input from player {}: {} ({} in a row)", player.name, violation, count);
+    let limit = state.config.kick_after_violations;
+    if limit > 0 && count >= limit {
+        kick(addr, state, transport, &format!("kicked: {}", violation)).await?;
+    }
+    Ok(())
+}
+
+async fn kick(addr: SocketAddr, state: &mut GameState, transport: &mut Transport, reason: &str) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(player) = state.players.remove(&addr) {
+        println!("Player {} was {}", player.name, reason);
+        state.mode.on_leave(&player);
+        transport.reject(addr, reason).await;
+        broadcast_player_left(state, transport, player.id).await?;
+    }
+    Ok(())
+}
+
+async fn broadcast_player_left(state: &GameState, transport: &mut Transport, id: PlayerId) -> Result<(), Box<dyn std::error::Error>> {
+    let left_message = ServerMessage::PlayerLeft { player: id };
     for addr in state.players.keys() {
-        socket.send_to(serialized.as_bytes(), addr).await?;
+        transport.send(*addr, &left_message).await;
     }
     Ok(())
 }
-pub async fn check_game_over(game_state: Arc<Mutex<GameState>>, socket: Arc<UdpSocket>) -> Result<(), Box<dyn std::error::Error>> {
-    let mut interval = tokio::time::interval(Duration::from_secs(1));
-    loop {
-        interval.tick().await;
-        let mut state = game_state.lock().await;
-        if state.is_game_over() {
-            let winner = state.players.values()
-                .max_by_key(|p| p.points)
-                .cloned();
-            if let Some(winner) = winner {
-                let game_over_message = ServerMessage::GameOver {
-                    winner: winner.name,
-                    scores: state.players.values().map(|p| (p.name.clone(), p.points)).collect(),
-                };
-                let serialized = serde_json::to_string(&game_over_message)?;
+
+pub async fn broadcast_game_state(
+    state: &mut GameState,
+    transport: &mut Transport,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let world: PackedWorld = state.players
+        .values()
+        .map(|player| (player.id, PackedPlayer::pack(&PlayerSnapshot {
+            x: player.position.0,
+            y: player.position.1,
+            rotation: player.rotation,
+            is_alive: player.is_alive,
+        })))
+        .collect();
+
+    // Chaque client ne reçoit que les joueurs qu'il peut voir ou entendre
+    let relevant: HashMap<SocketAddr, PackedWorld> = state.players
+        .iter()
+        .map(|(addr, viewer)| {
+            let visible = state.players
+                .values()
+                .filter(|target| visibility::is_relevant(&state.map, &state.config, viewer, target))
+                .map(|target| (target.id, world[&target.id]))
+                .collect();
+            (*addr, visible)
+        })
+        .collect();
+
+    // Envoyer ce qui a changé depuis le dernier snapshot confirmé par chaque client,
+    // avec le numéro de sa dernière entrée traitée pour sa réconciliation et ses munitions
+    let (tick, clock, match_clock) = (state.tick, state.clock(), state.match_clock());
+    for (addr, player) in state.players.iter_mut().filter(|(_, player)| player.map_loaded) {
+        let world = &relevant[addr];
+        let baseline = player.snapshots.baseline(|sequence| transport.was_acked(*addr, sequence));
+        let (players, removed) = snapshot::diff(baseline.map(|(_, world)| world), world);
+        let game_state_message = ServerMessage::GameState {
+            tick,
+            baseline: baseline.map(|(tick, _)| tick),
+            players,
+            removed,
+            last_input: player.last_input,
+            ammo: player.loadout.status(clock),
+            clock: match_clock,
+        };
+        if let Some(sequence) = transport.send(*addr, &game_state_message).await {
+            player.snapshots.record(sequence, tick, world.clone());
+        }
+    }
+    Ok(())
+}
+/// Moves the match to its next phase once the current one is over:
+/// WaitingForPlayers, Countdown, InProgress, PostMatch, then waiting again
+/// on a new map.
+async fn advance_phase(state: &mut GameState, transport: &mut Transport) -> Result<(), Box<dyn std::error::Error>> {
+    let enough_players = state.players.len() >= state.config.min_players;
+    let over = state.phase_duration().is_some_and(|duration| state.phase_started.elapsed() >= duration);
+    let next = match state.phase {
+        MatchPhase::WaitingForPlayers if enough_players => MatchPhase::Countdown,
+        MatchPhase::Countdown if !enough_players => MatchPhase::WaitingForPlayers,
+        MatchPhase::Countdown if over => {
+            // Le mode peut rééquilibrer les équipes avant les apparitions
+            let teams: HashMap<PlayerId, Option<TeamId>> = state.players.values().map(|player| (player.id, player.team)).collect();
+            state.mode.on_match_start(&mut state.players);
+            for player in state.players.values().filter(|player| teams.get(&player.id) != Some(&player.team)) {
+                println!("Player {} moved to team {:?}", player.name, player.team);
+                let team_message = ServerMessage::PlayerTeam { player: player.id, team: player.team };
                 for addr in state.players.keys() {
-                    socket.send_to(serialized.as_bytes(), addr).await?;
+                    transport.send(*addr, &team_message).await;
                 }
-                // Réinitialiser le jeu
-                *state = GameState::new(state.difficulty);
             }
+            respawn_everyone(state, transport, true).await;
+            MatchPhase::InProgress
+        }
+        // Plus personne à qui montrer les scores
+        MatchPhase::InProgress if state.players.is_empty() => MatchPhase::WaitingForPlayers,
+        MatchPhase::InProgress if !enough_players || over || state.mode.is_won(&state.players) => {
+            send_game_over(state, transport).await;
+            MatchPhase::PostMatch
         }
+        MatchPhase::PostMatch if over => {
+            // Nouvelle carte : chaque client la recharge comme après Welcome
+            state.new_map();
+            for addr in state.players.keys() {
+                send_map(*addr, state, transport).await;
+            }
+            respawn_everyone(state, transport, false).await;
+            MatchPhase::WaitingForPlayers
+        }
+        _ => return Ok(()),
+    };
+    println!("Match phase: {:?} -> {:?}", state.phase, next);
+    state.phase = next;
+    state.phase_started = Instant::now();
+    let phase_message = ServerMessage::PhaseChanged { phase: next };
+    for addr in state.players.keys() {
+        transport.send(*addr, &phase_message).await;
+    }
+    Ok(())
+}
+
+async fn send_game_over(state: &GameState, transport: &mut Transport) {
+    let winner = state.mode.winner(&state.players);
+    println!("{} match over, winner: {:?}", state.mode.name(), winner);
+    let game_over_message = ServerMessage::GameOver {
+        winner,
+        scoreboard: state.mode.scoreboard(&state.players),
+    };
+    for addr in state.players.keys() {
+        transport.send(*addr, &game_over_message).await;
     }
-}
\ No newline at end of file
+}
+
+/// Puts every player back on a spawn point with full health, on a new map
+/// or at the start of a match. A new match also resets the scores, kills
+/// and deaths.
+async fn respawn_everyone(state: &mut GameState, transport: &mut Transport, reset_points: bool) {
+    let addrs: Vec<SocketAddr> = state.players.keys().copied().collect();
+    let mut spawned: Vec<((f32, f32), Option<TeamId>)> = Vec::new();
+    for &addr in &addrs {
+        let Some(team) = state.players.get(&addr).map(|player| player.team) else {
+            continue;
+        };
+        let (teammates, enemies): (Vec<_>, Vec<_>) = spawned.iter().partition(|(_, other)| are_teammates(*other, team));
+        let positions = |spawned: Vec<&((f32, f32), Option<TeamId>)>| spawned.iter().map(|(position, _)| *position).collect::<Vec<_>>();
+        let position = generate_safe_spawn_point(&state.map, &state.config, &positions(enemies), &positions(teammates));
+        spawned.push((position, team));
+        let protected_until = Instant::now() + state.config.spawn_protection;
+        let Some(player) = state.players.get_mut(&addr) else {
+            continue;
+        };
+        player.position = position;
+        player.is_alive = true;
+        player.health = MAX_HEALTH;
+        player.armor = SPAWN_ARMOR;
+        player.loadout = Loadout::default();
+        player.died_at = None;
+        player.protected_until = Some(protected_until);
+        if reset_points {
+            player.points = 0;
+            player.kills = 0;
+            player.deaths = 0;
+        }
+    }
+    // Une fois tout le monde placé, pour juger qui voit qui
+    for addr in addrs {
+        send_respawn(addr, state, transport).await;
+    }
+}
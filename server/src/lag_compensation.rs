@@ -0,0 +1,28 @@
+use std::collections::{HashMap, VecDeque};
+use maze_wars_protocol::PlayerId;
+
+pub type Positions = HashMap<PlayerId, (f32, f32)>;
+
+/// Where every player stood at each of the last few ticks, so shots can be
+/// tested against what the shooter was actually looking at.
+#[derive(Default)]
+pub struct PositionHistory {
+    ticks: VecDeque<(u64, Positions)>,
+}
+
+impl PositionHistory {
+    pub fn record(&mut self, tick: u64, positions: Positions, capacity: usize) {
+        self.ticks.push_back((tick, positions));
+        while self.ticks.len() > capacity.max(1) {
+            self.ticks.pop_front();
+        }
+    }
+
+    pub fn positions_at(&self, tick: u64) -> Option<&Positions> {
+        self.ticks
+            .iter()
+            .rev()
+            .find(|(recorded, _)| *recorded == tick)
+            .map(|(_, positions)| positions)
+    }
+}
@@ -1,14 +1,20 @@
+mod config;
+mod game_mode;
 mod game_state;
 mod map;
 mod player;
-mod messages;
 mod network;
 mod handlers;
+mod lag_compensation;
+mod snapshots;
+mod validation;
+mod visibility;
 
 use tokio::net::UdpSocket;
 use std::sync::Arc;
 use tokio::sync::Mutex;
 use std::io::{self, Write};
+use maze_wars_protocol::DEFAULT_PORT;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -19,11 +25,13 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     io::stdin().read_line(&mut input)?;
     let difficulty: u8 = input.trim().parse().unwrap_or(2);
 
-    let socket = UdpSocket::bind("0.0.0.0:34254").await?;
+    let socket = UdpSocket::bind(("0.0.0.0", DEFAULT_PORT)).await?;
     let socket = Arc::new(socket);
-    let game_state = Arc::new(Mutex::new(game_state::GameState::new(difficulty)));
+    let config = config::ServerConfig::from_args();
+    let game_state = Arc::new(Mutex::new(game_state::GameState::new(difficulty, config.clone())));
 
-    println!("Server listening on {}", socket.local_addr()?);
+    println!("Server listening on {} at {} ticks per second, mode {}", socket.local_addr()?, config.tick_rate, config.mode);
 
-    network::start_server(socket, game_state).await
+    let stats = Arc::new(network::NetworkStats::default());
+    network::start_server(socket, game_state, config, stats).await
 }
\ No newline at end of file
@@ -1,27 +1,373 @@
 use tokio::net::UdpSocket;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
+use tokio::sync::mpsc::{self, error::TrySendError, Receiver};
+use maze_wars_protocol::codec::{self, CodecError, PROTOCOL_VERSION};
+use maze_wars_protocol::reliability::{Endpoint, Received};
+use maze_wars_protocol::{ClientMessage, ServerMessage, MAX_PACKET_SIZE};
+use crate::config::ServerConfig;
 use crate::game_state::GameState;
 use crate::handlers;
-use crate::messages::ClientMessage;
+
+const STATS_REPORT_INTERVAL: Duration = Duration::from_secs(60);
+/// Packets waiting for the next tick. A flood beyond this is dropped instead
+/// of growing the queue without bound.
+const PACKET_QUEUE_SIZE: usize = 4096;
+
+/// Packet counters shared by the receive loop and the simulation loop.
+#[derive(Default, Debug)]
+pub struct NetworkStats {
+    pub received: AtomicU64,
+    pub dropped: AtomicU64,   // well-formed but from another protocol version
+    pub malformed: AtomicU64, // failed to decode
+    pub oversized: AtomicU64, // larger than MAX_PACKET_SIZE
+    pub queue_full: AtomicU64, // arrived while PACKET_QUEUE_SIZE packets were waiting for the next tick
+    pub send_failures: AtomicU64,
+}
+
+impl NetworkStats {
+    fn count(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn rejected_total(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+            + self.malformed.load(Ordering::Relaxed)
+            + self.oversized.load(Ordering::Relaxed)
+            + self.queue_full.load(Ordering::Relaxed)
+            + self.send_failures.load(Ordering::Relaxed)
+    }
+
+    pub fn summary(&self) -> String {
+        format!(
+            "received {}, dropped {}, malformed {}, oversized {}, queue full {}, failed sends {}",
+            self.received.load(Ordering::Relaxed),
+            self.dropped.load(Ordering::Relaxed),
+            self.malformed.load(Ordering::Relaxed),
+            self.oversized.load(Ordering::Relaxed),
+            self.queue_full.load(Ordering::Relaxed),
+            self.send_failures.load(Ordering::Relaxed),
+        )
+    }
+}
+
+/// Owns the socket and the reliability state kept for every client address.
+pub struct Transport {
+    socket: Arc<UdpSocket>,
+    endpoints: HashMap<SocketAddr, Endpoint>,
+    stats: Arc<NetworkStats>,
+}
+
+impl Transport {
+    pub fn new(socket: Arc<UdpSocket>, stats: Arc<NetworkStats>) -> Self {
+        Self { socket, endpoints: HashMap::new(), stats }
+    }
+
+    /// Sends `message` to `addr` and returns the sequence of the packet.
+    /// Failures are logged and counted but never returned: a client that
+    /// went away must not stop the simulation.
+    pub async fn send(&mut self, addr: SocketAddr, message: &ServerMessage) -> Option<u16> {
+        let endpoint = self.endpoints.entry(addr).or_default();
+        let sequence = endpoint.next_sequence();
+        match endpoint.send(message, Instant::now()) {
+            Ok(packet) => {
+                self.send_packet(addr, &packet).await;
+                Some(sequence)
+            }
+            Err(e) => {
+                NetworkStats::count(&self.stats.send_failures);
+                eprintln!("Failed to encode message for {}: {}", addr, e);
+                None
+            }
+        }
+    }
+
+    pub fn was_acked(&self, addr: SocketAddr, sequence: u16) -> bool {
+        self.endpoints.get(&addr).is_some_and(|endpoint| endpoint.was_acked(sequence))
+    }
+
+    async fn send_packet(&self, addr: SocketAddr, packet: &[u8]) {
+        if let Err(e) = self.socket.send_to(packet, addr).await {
+            NetworkStats::count(&self.stats.send_failures);
+            eprintln!("Failed to send to {}: {}", addr, e);
+        }
+    }
+
+    /// Moves the reliability state kept for `from` to `to`.
+    pub fn rebind(&mut self, from: SocketAddr, to: SocketAddr) {
+        if let Some(endpoint) = self.endpoints.remove(&from) {
+            self.endpoints.insert(to, endpoint);
+        }
+    }
+
+    /// Drops the reliability state of every address `keep` rejects.
+    pub fn retain(&mut self, mut keep: impl FnMut(&SocketAddr) -> bool) {
+        self.endpoints.retain(|addr, _| keep(addr));
+    }
+
+    /// Sends `reason` the way an incompatible `Join` is refused, which makes
+    /// the client give up, and forgets the address.
+    pub async fn reject(&mut self, addr: SocketAddr, reason: &str) {
+        self.send_packet(addr, &codec::encode_rejection(reason)).await;
+        self.forget(addr);
+    }
+
+    pub fn forget(&mut self, addr: SocketAddr) {
+        self.endpoints.remove(&addr);
+    }
+
+    pub fn receive(&mut self, addr: SocketAddr, bytes: &[u8]) -> Result<Option<Received<ClientMessage>>, CodecError> {
+        let result = self.endpoints.entry(addr).or_default().receive(bytes, Instant::now());
+        if result.is_err() {
+            NetworkStats::count(&self.stats.malformed);
+        }
+        result
+    }
+
+    /// Re-sends unacknowledged reliable messages, and acks clients that
+    /// have not been sent anything recently.
+    pub async fn flush(&mut self) {
+        let now = Instant::now();
+        let mut packets = Vec::new();
+        for (addr, endpoint) in self.endpoints.iter_mut() {
+            for packet in endpoint.resend_due(now) {
+                packets.push((*addr, packet));
+            }
+            if endpoint.ack_due(now) {
+                packets.push((*addr, endpoint.ack(now)));
+            }
+        }
+        for (addr, packet) in packets {
+            self.send_packet(addr, &packet).await;
+        }
+    }
+}
 
 pub async fn start_server(
     socket: Arc<UdpSocket>,
-    game_state: Arc<Mutex<GameState>>
+    game_state: Arc<Mutex<GameState>>,
+    config: ServerConfig,
+    stats: Arc<NetworkStats>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let game_state_clone = Arc::clone(&game_state);
-    let socket_clone = Arc::clone(&socket);
+    let (packet_sender, packet_receiver) = mpsc::channel(PACKET_QUEUE_SIZE);
+    let transport = Transport::new(Arc::clone(&socket), Arc::clone(&stats));
+
+    tokio::spawn(run_ticks(game_state, transport, packet_receiver, config));
 
-    tokio::spawn(async move {
-        if let Err(e) = handlers::check_game_over(game_state_clone, socket_clone).await {
-            eprintln!("Error in game over check: {}", e);
+    // One spare byte tells a datagram that exactly fills the buffer from one that was cut
+    let mut buf = vec![0u8; MAX_PACKET_SIZE + 1];
+    loop {
+        let (len, addr) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e) => {
+                // e.g. ICMP port unreachable reported for a client that went away
+                eprintln!("Failed to receive data: {}", e);
+                continue;
+            }
+        };
+        NetworkStats::count(&stats.received);
+        if len > MAX_PACKET_SIZE {
+            NetworkStats::count(&stats.oversized);
+            continue;
+        }
+        let header = match codec::read_header(&buf[..len]) {
+            Ok(header) => header,
+            Err(_) => {
+                NetworkStats::count(&stats.malformed);
+                continue;
+            }
+        };
+        if header.version != PROTOCOL_VERSION {
+            NetworkStats::count(&stats.dropped);
+            // Only answer handshakes, anything else from an outdated client is dropped
+            if header.kind == ClientMessage::KIND_JOIN {
+                let reason = format!(
+                    "server speaks protocol v{} but client sent v{}, please update your client",
+                    PROTOCOL_VERSION, header.version
+                );
+                println!("Rejected join from {}: {}", addr, reason);
+                if let Err(e) = socket.send_to(&codec::encode_rejection(&reason), addr).await {
+                    NetworkStats::count(&stats.send_failures);
+                    eprintln!("Failed to send rejection to {}: {}", addr, e);
+                }
+            }
+            continue;
         }
-    });
+        // Packets are only decoded and applied by the simulation loop, at the start of the next tick
+        match packet_sender.try_send((addr, buf[..len].to_vec())) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => NetworkStats::count(&stats.queue_full),
+            Err(TrySendError::Closed(_)) => return Err("simulation loop stopped".into()),
+        }
+    }
+}
 
+/// Fixed-timestep simulation: every tick applies the inputs received since
+/// the previous one, advances the world and sends a single snapshot.
+async fn run_ticks(
+    game_state: Arc<Mutex<GameState>>,
+    mut transport: Transport,
+    mut packets: Receiver<(SocketAddr, Vec<u8>)>,
+    config: ServerConfig,
+) {
+    let mut interval = tokio::time::interval(config.tick_interval());
+    let mut last_report = (Instant::now(), 0);
     loop {
-        let mut buf = vec![0u8; 4096];
-        let (len, addr) = socket.recv_from(&mut buf).await?;
-        let message: ClientMessage = serde_json::from_slice(&buf[..len])?;
-        handlers::handle_message(message, addr, Arc::clone(&game_state), Arc::clone(&socket)).await?;
+        interval.tick().await;
+        let mut state = game_state.lock().await;
+        while let Ok((addr, bytes)) = packets.try_recv() {
+            if let Ok((prefix, header)) = codec::read_headers(&bytes) {
+                if let Some(session) = header.session {
+                    handlers::rebind_session(session, addr, &mut state, &mut transport);
+                }
+                // A client reconnecting starts over with a fresh endpoint, the
+                // server must do the same (retransmissions have a later sequence)
+                if prefix.kind == ClientMessage::KIND_RECONNECT && header.sequence == 0 {
+                    transport.forget(addr);
+                }
+            }
+            let result = transport.receive(addr, &bytes);
+            if result.is_ok() {
+                if let Some(player) = state.players.get_mut(&addr) {
+                    player.last_seen = Instant::now();
+                }
+            }
+            match result {
+                Ok(Some(received)) => {
+                    if let Err(e) = handlers::handle_message(received.message, addr, &mut state, &mut transport).await {
+                        eprintln!("Failed to handle message from {}: {}", addr, e);
+                    }
+                }
+                Ok(None) => {}
+                Err(e) => eprintln!("Dropping packet from {}: {}", addr, e),
+            }
+        }
+        // Une erreur ne doit pas arrêter la simulation pour tout le monde
+        if let Err(e) = handlers::step(&mut state, &mut transport).await {
+            eprintln!("Failed to step the simulation: {}", e);
+        }
+        if let Err(e) = handlers::broadcast_game_state(&mut state, &mut transport).await {
+            eprintln!("Failed to broadcast game state: {}", e);
+        }
+        transport.flush().await;
+
+        let rejected = transport.stats.rejected_total();
+        if last_report.0.elapsed() >= STATS_REPORT_INTERVAL && rejected != last_report.1 {
+            println!("Network stats: {}", transport.stats.summary());
+            last_report = (Instant::now(), rejected);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maze_wars_protocol::codec::SequenceHeader;
+    use maze_wars_protocol::{PlayerId, SessionToken};
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    async fn join(server: SocketAddr, name: &str) -> (UdpSocket, Endpoint) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut endpoint = Endpoint::new();
+        let join = endpoint.send(&ClientMessage::Join { name: name.to_string() }, Instant::now()).unwrap();
+        socket.send_to(&join, server).await.unwrap();
+        (socket, endpoint)
+    }
+
+    /// Returns the player id and session of the first `Welcome` received.
+    async fn wait_for_welcome(socket: &UdpSocket, endpoint: &mut Endpoint) -> Option<(PlayerId, SessionToken)> {
+        let mut buf = vec![0u8; MAX_PACKET_SIZE];
+        let deadline = tokio::time::sleep(Duration::from_secs(2));
+        tokio::pin!(deadline);
+        loop {
+            tokio::select! {
+                _ = &mut deadline => return None,
+                received = socket.recv_from(&mut buf) => {
+                    let (len, _) = received.unwrap();
+                    if let Ok(Some(received)) = endpoint.receive::<ServerMessage>(&buf[..len], Instant::now()) {
+                        if let ServerMessage::Welcome { player_id, session, .. } = received.message {
+                            return Some((player_id, session));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    async fn spawn_server() -> (SocketAddr, Arc<Mutex<GameState>>, Arc<NetworkStats>) {
+        let socket = Arc::new(UdpSocket::bind("127.0.0.1:0").await.unwrap());
+        let server = socket.local_addr().unwrap();
+        let config = ServerConfig::default();
+        let game_state = Arc::new(Mutex::new(GameState::new(1, config.clone())));
+        let stats = Arc::new(NetworkStats::default());
+        let (server_state, server_stats) = (Arc::clone(&game_state), Arc::clone(&stats));
+        tokio::spawn(async move {
+            let _ = start_server(socket, server_state, config, server_stats).await;
+        });
+        (server, game_state, stats)
+    }
+
+    #[tokio::test]
+    async fn survives_malformed_and_oversized_datagrams() {
+        let (server, _, stats) = spawn_server().await;
+
+        // A joined client that then sends garbage bodies behind valid headers
+        let (victim, mut victim_endpoint) = join(server, "victim").await;
+        assert!(wait_for_welcome(&victim, &mut victim_endpoint).await.is_some());
+
+        let attacker = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let mut rng = StdRng::seed_from_u64(10);
+        for i in 0..300u16 {
+            let len = rng.gen_range(0..64);
+            let mut bytes: Vec<u8> = (0..len).map(|_| rng.gen()).collect();
+            if i % 3 == 0 {
+                let mut valid = codec::encode(&SequenceHeader { sequence: i, ..Default::default() }, &ClientMessage::Heartbeat).unwrap();
+                valid.pop();
+                valid[6] = rng.gen_range(0..6);
+                valid.extend_from_slice(&bytes);
+                bytes = valid;
+                victim.send_to(&bytes, server).await.unwrap();
+            } else {
+                attacker.send_to(&bytes, server).await.unwrap();
+            }
+        }
+        attacker.send_to(&vec![0u8; MAX_PACKET_SIZE + 100], server).await.unwrap();
+
+        let (fresh, mut fresh_endpoint) = join(server, "fresh").await;
+        assert!(wait_for_welcome(&fresh, &mut fresh_endpoint).await.is_some(), "server stopped answering joins");
+        assert!(stats.malformed.load(Ordering::Relaxed) > 0);
+        assert_eq!(stats.oversized.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn session_follows_the_player_to_a_new_address() {
+        let (server, game_state, _) = spawn_server().await;
+        let (socket, mut endpoint) = join(server, "alice").await;
+        let (id, session) = wait_for_welcome(&socket, &mut endpoint).await.unwrap();
+        endpoint.set_session(session);
+
+        // Same endpoint, new source address: the player keeps their identity
+        let moved = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let heartbeat = endpoint.send(&ClientMessage::Heartbeat, Instant::now()).unwrap();
+        moved.send_to(&heartbeat, server).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        {
+            let state = game_state.lock().await;
+            assert_eq!(state.players.len(), 1);
+            assert_eq!(state.players[&moved.local_addr().unwrap()].id, id);
+        }
+
+        // Reconnecting from a fresh endpoint resumes the same player
+        let reconnected = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        endpoint.reset();
+        let reconnect = endpoint.send(&ClientMessage::Reconnect { session, name: "alice".to_string() }, Instant::now()).unwrap();
+        reconnected.send_to(&reconnect, server).await.unwrap();
+        assert_eq!(wait_for_welcome(&reconnected, &mut endpoint).await, Some((id, session)));
+        assert_eq!(game_state.lock().await.players.len(), 1);
     }
-}
\ No newline at end of file
+}
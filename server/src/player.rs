@@ -1,13 +1,78 @@
-use serde::{Serialize, Deserialize};
+use std::time::Instant;
+use maze_wars_protocol::weapon::Loadout;
+use maze_wars_protocol::{PlayerId, SessionToken, TeamId};
+use crate::snapshots::SentSnapshots;
+use crate::validation::{MoveBudget, Violations};
 
-pub const PLAYER_SPEED: f32 = 0.3;
-pub const SHOOT_RANGE: f32 = 10.0;
-
-#[derive(Clone, Serialize, Deserialize, Debug)]
+#[derive(Clone, Debug)]
 pub struct Player {
-    pub name: String,
+    pub id: PlayerId,
+    pub name: String, // unique among connected players, display only
+    pub session: SessionToken,
     pub position: (f32, f32),
     pub is_alive: bool,
+    pub team: Option<TeamId>, // set by the game mode, None in modes without teams
     pub points: u32,
-    pub  rotation: f32
-}
\ No newline at end of file
+    pub kills: u32,
+    pub deaths: u32,
+    pub rotation: f32,
+    pub last_input: u32, // sequence of the last Move applied
+    pub last_seen: Instant, // last packet of any kind from this player
+    pub last_shot: Option<Instant>, // shooting makes a player audible, see `visibility`
+    pub map_loaded: bool, // the client acknowledged the whole map, snapshots can start
+    pub snapshots: SentSnapshots,
+    pub move_budget: MoveBudget,
+    pub violations: Violations,
+    pub loadout: Loadout,
+    pub health: u32,
+    pub armor: u32,
+    pub died_at: Option<Instant>, // respawns `respawn_delay` after this
+    pub protected_until: Option<Instant>, // spawn protection, shots go through the player until then
+}
+
+impl Player {
+    pub fn is_protected(&self, now: Instant) -> bool {
+        self.protected_until.is_some_and(|until| now < until)
+    }
+
+    pub fn is_teammate(&self, other: &Player) -> bool {
+        are_teammates(self.team, other.team)
+    }
+}
+
+/// Players without a team are nobody's teammates.
+pub fn are_teammates(a: Option<TeamId>, b: Option<TeamId>) -> bool {
+    a.is_some() && a == b
+}
+
+#[cfg(test)]
+impl Player {
+    /// An alive player at (1, 1) with the map loaded.
+    pub fn for_tests(id: PlayerId) -> Self {
+        use std::time::Duration;
+        Self {
+            id,
+            name: format!("player {}", id),
+            session: id as u64,
+            position: (1.0, 1.0),
+            team: None,
+            is_alive: true,
+            points: 0,
+            kills: 0,
+            deaths: 0,
+            rotation: 0.0,
+            last_input: 0,
+            last_seen: Instant::now(),
+            last_shot: None,
+            map_loaded: true,
+            snapshots: SentSnapshots::default(),
+            move_budget: MoveBudget::full(Duration::from_millis(400)),
+            violations: Violations::default(),
+            loadout: Loadout::default(),
+            health: 100,
+            armor: 0,
+            died_at: None,
+            protected_until: None,
+        }
+    }
+}
@@ -0,0 +1,34 @@
+use std::collections::VecDeque;
+use maze_wars_protocol::snapshot::{PackedWorld, SNAPSHOT_HISTORY};
+
+/// Snapshots recently sent to one client, with the sequence of the packet
+/// that carried them, to delta-encode the next one against the newest
+/// snapshot the client acknowledged.
+#[derive(Clone, Debug, Default)]
+pub struct SentSnapshots {
+    sent: VecDeque<(u16, u64, PackedWorld)>,
+}
+
+impl SentSnapshots {
+    pub fn record(&mut self, sequence: u16, tick: u64, world: PackedWorld) {
+        if self.sent.len() == SNAPSHOT_HISTORY {
+            self.sent.pop_front();
+        }
+        self.sent.push_back((sequence, tick, world));
+    }
+
+    /// Tick and content of the newest acknowledged snapshot. `None` on join
+    /// or when every recent snapshot was lost: a full snapshot is needed.
+    pub fn baseline(&self, was_acked: impl Fn(u16) -> bool) -> Option<(u64, &PackedWorld)> {
+        self.sent
+            .iter()
+            .rev()
+            .find(|(sequence, _, _)| was_acked(*sequence))
+            .map(|(_, tick, world)| (*tick, world))
+    }
+
+    /// Forgets everything, for when the client's packet sequences start over.
+    pub fn clear(&mut self) {
+        self.sent.clear();
+    }
+}
@@ -0,0 +1,152 @@
+//! Checks on client input the server cannot take at face value.
+//!
+//! A `Move` direction must be finite and is clamped to unit length, and a
+//! player can only move as often as time allows: every tick refills a
+//! budget of movement time that each `Move` spends `MOVE_INTERVAL` of.
+//! Shots must be aimed with a finite pitch and respect the weapon's fire rate, reloads and ammunition (see
+//! `weapon::Loadout`). Inputs that fail these checks are dropped and count
+//! as violations.
+
+use std::fmt;
+use std::time::Duration;
+use maze_wars_protocol::player::MOVE_INTERVAL;
+use maze_wars_protocol::weapon::FireError;
+
+/// Taken off fire intervals and reload times, in seconds: shots sent at the
+/// right pace can still arrive bunched up.
+pub const FIRE_LEEWAY: f64 = 0.1;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Violation {
+    InvalidDirection,
+    InvalidPitch,
+    TooFast,
+    UnknownWeapon,
+    FiringTooFast,
+    FiringWhileReloading,
+    NoAmmo,
+}
+
+impl From<FireError> for Violation {
+    fn from(error: FireError) -> Self {
+        match error {
+            FireError::UnknownWeapon => Violation::UnknownWeapon,
+            FireError::TooSoon => Violation::FiringTooFast,
+            FireError::Reloading => Violation::FiringWhileReloading,
+            FireError::Empty => Violation::NoAmmo,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::InvalidDirection => write!(f, "invalid move direction"),
+            Violation::InvalidPitch => write!(f, "invalid aim pitch"),
+            Violation::TooFast => write!(f, "moving faster than allowed"),
+            Violation::UnknownWeapon => write!(f, "unknown weapon"),
+            Violation::FiringTooFast => write!(f, "firing faster than the weapon allows"),
+            Violation::FiringWhileReloading => write!(f, "firing while reloading"),
+            Violation::NoAmmo => write!(f, "firing without ammunition"),
+        }
+    }
+}
+
+/// `direction` scaled down to unit length if longer.
+pub fn sanitize_direction(direction: (f32, f32)) -> Result<(f32, f32), Violation> {
+    if !direction.0.is_finite() || !direction.1.is_finite() {
+        return Err(Violation::InvalidDirection);
+    }
+    let length = (direction.0 * direction.0 + direction.1 * direction.1).sqrt();
+    if length > 1.0 {
+        Ok((direction.0 / length, direction.1 / length))
+    } else {
+        Ok(direction)
+    }
+}
+
+/// Movement time a player is allowed to spend, in seconds.
+#[derive(Debug, Clone, Copy)]
+pub struct MoveBudget {
+    seconds: f32,
+}
+
+impl MoveBudget {
+    pub fn full(cap: Duration) -> Self {
+        Self { seconds: cap.as_secs_f32() }
+    }
+
+    /// Adds the time elapsed, up to `cap` so an idle player cannot save up
+    /// for a burst.
+    pub fn refill(&mut self, elapsed: Duration, cap: Duration) {
+        self.seconds = (self.seconds + elapsed.as_secs_f32()).min(cap.as_secs_f32());
+    }
+
+    pub fn spend_move(&mut self) -> Result<(), Violation> {
+        // Tolérance pour les erreurs d'arrondi
+        if self.seconds + 1e-4 < MOVE_INTERVAL {
+            return Err(Violation::TooFast);
+        }
+        self.seconds -= MOVE_INTERVAL;
+        Ok(())
+    }
+}
+
+/// Rejected inputs since the last accepted one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Violations {
+    count: u32,
+}
+
+impl Violations {
+    /// Records one violation and returns how many happened in a row.
+    pub fn record(&mut self) -> u32 {
+        self.count += 1;
+        self.count
+    }
+
+    /// An input was accepted: the next violation starts a new streak.
+    pub fn clear(&mut self) {
+        self.count = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directions_are_clamped() {
+        assert_eq!(sanitize_direction((30.0, 40.0)), Ok((0.6, 0.8)));
+        assert_eq!(sanitize_direction((0.3, 0.0)), Ok((0.3, 0.0)));
+        assert_eq!(sanitize_direction((f32::NAN, 0.0)), Err(Violation::InvalidDirection));
+        assert_eq!(sanitize_direction((0.0, f32::INFINITY)), Err(Violation::InvalidDirection));
+    }
+
+    #[test]
+    fn moves_are_limited_by_elapsed_time() {
+        let cap = Duration::from_millis(400);
+        let mut budget = MoveBudget::full(cap);
+        let burst = (0..20).filter(|_| budget.spend_move().is_ok()).count();
+        assert_eq!(burst, 5);
+
+        // One tick of 33ms is not enough for another move, three are
+        budget.refill(Duration::from_millis(33), cap);
+        assert_eq!(budget.spend_move(), Err(Violation::TooFast));
+        budget.refill(Duration::from_millis(66), cap);
+        assert_eq!(budget.spend_move(), Ok(()));
+
+        // Idling does not buy more than the cap
+        budget.refill(Duration::from_secs(60), cap);
+        assert_eq!((0..20).filter(|_| budget.spend_move().is_ok()).count(), 5);
+    }
+
+    #[test]
+    fn accepted_inputs_reset_violations() {
+        let mut violations = Violations::default();
+        assert_eq!(violations.record(), 1);
+        assert_eq!(violations.record(), 2);
+        violations.clear();
+        assert_eq!(violations.record(), 1);
+    }
+}
@@ -0,0 +1,77 @@
+//! Interest management: which players each client is told about.
+//!
+//! A client only receives the players it could plausibly see, in line of
+//! sight and within `view_radius`, plus those it could hear because they
+//! just shot nearby and its teammates wherever they are. Everyone else is
+//! left out of its snapshots, so a modified client cannot show enemies
+//! behind walls.
+
+use maze_wars_protocol::map::PLAYER_SIZE;
+use maze_wars_protocol::Map;
+use crate::config::ServerConfig;
+use crate::player::Player;
+
+pub fn is_relevant(map: &Map, config: &ServerConfig, viewer: &Player, target: &Player) -> bool {
+    // Les coéquipiers sont toujours connus, pour la minicarte
+    if viewer.id == target.id || viewer.is_teammate(target) {
+        return true;
+    }
+    let audible = target.last_shot.is_some_and(|shot| shot.elapsed() < config.audible_for);
+    if audible && distance(viewer.position, target.position) <= config.hearing_radius {
+        return true;
+    }
+    can_see(map, config, viewer.position, target.position)
+}
+
+/// Whether any of the centre or the two sides of a player standing at `to`
+/// is visible from `from`, so players do not pop in when half hidden.
+pub fn can_see(map: &Map, config: &ServerConfig, from: (f32, f32), to: (f32, f32)) -> bool {
+    let length = distance(from, to);
+    if length > config.view_radius {
+        return false;
+    }
+    if length == 0.0 {
+        return true;
+    }
+    // Perpendiculaire à la ligne de vue, de la demi-largeur du joueur
+    let side = (-(to.1 - from.1) / length * PLAYER_SIZE / 2.0, (to.0 - from.0) / length * PLAYER_SIZE / 2.0);
+    [(0.0, 0.0), side, (-side.0, -side.1)].iter().any(|offset| {
+        let point = (to.0 + offset.0, to.1 + offset.1);
+        let direction = (point.0 - from.0, point.1 - from.1);
+        map.raycast(from, direction, distance(from, point)).is_none()
+    })
+}
+
+fn distance(a: (f32, f32), b: (f32, f32)) -> f32 {
+    ((a.0 - b.0).powi(2) + (a.1 - b.1).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn open_map() -> Map {
+        let cells = (0..20).map(|y| (0..20).map(|x| x == 0 || y == 0 || x == 19 || y == 19).collect()).collect();
+        Map::from_cells(cells, 0)
+    }
+
+    #[test]
+    fn walls_hide_players() {
+        let mut map = open_map();
+        for row in map.cells[2..18].iter_mut() {
+            row[10] = true;
+        }
+        let config = ServerConfig::default();
+        assert!(!can_see(&map, &config, (5.0, 10.0), (15.0, 10.0)));
+        assert!(can_see(&map, &config, (5.0, 10.0), (8.0, 4.0)));
+        // Around the end of the wall
+        assert!(can_see(&map, &config, (9.0, 18.0), (11.0, 18.0)));
+    }
+
+    #[test]
+    fn far_players_are_hidden() {
+        let config = ServerConfig { view_radius: 5.0, ..ServerConfig::default() };
+        assert!(can_see(&open_map(), &config, (2.0, 2.0), (2.0, 6.5)));
+        assert!(!can_see(&open_map(), &config, (2.0, 2.0), (2.0, 7.5)));
+    }
+}